@@ -0,0 +1,195 @@
+//! DER
+use num_bigint::BigUint;
+use crate::error::Error;
+
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_SEQUENCE: u8 = 0x30;
+
+/// Encodes a Tag-Length-Value triplet using the DER definite length form.
+pub fn encode_tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut res = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        res.push(len as u8);
+    } else {
+        let len_bytes: Vec<u8> = len.to_be_bytes().iter().skip_while(|b| **b == 0).cloned().collect();
+        res.push(0x80 | len_bytes.len() as u8);
+        res.extend_from_slice(&len_bytes);
+    }
+    res.extend_from_slice(content);
+    res
+}
+
+/// Encodes a BigUint as a DER INTEGER, adding a leading zero when the high bit is set.
+pub fn encode_integer(x: &BigUint) -> Vec<u8> {
+    let mut bytes = x.to_bytes_be();
+    if bytes[0] & 0x80 != 0 {
+        bytes.insert(0, 0x00);
+    }
+    encode_tlv(TAG_INTEGER, &bytes)
+}
+
+/// Encodes the concatenation of already encoded elements as a DER SEQUENCE.
+pub fn encode_sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    encode_tlv(TAG_SEQUENCE, &items.concat())
+}
+
+/// Encodes an OBJECT IDENTIFIER from the bytes of its already encoded arcs.
+pub fn encode_oid(oid: &[u8]) -> Vec<u8> {
+    encode_tlv(TAG_OID, oid)
+}
+
+/// Encodes a DER NULL.
+pub fn encode_null() -> Vec<u8> {
+    encode_tlv(TAG_NULL, &[])
+}
+
+/// Encodes a BIT STRING without unused bits.
+pub fn encode_bit_string(content: &[u8]) -> Vec<u8> {
+    let mut bits = vec![0x00];
+    bits.extend_from_slice(content);
+    encode_tlv(TAG_BIT_STRING, &bits)
+}
+
+/// Encodes an explicitly tagged context-specific element [tag_number].
+pub fn encode_explicit(tag_number: u8, content: &[u8]) -> Vec<u8> {
+    encode_tlv(0xa0 | tag_number, content)
+}
+
+/// Reads DER encoded elements one after the other from a buffer.
+pub struct DerReader<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> DerReader<'a> {
+    /// Creates a reader over a DER encoded buffer.
+    pub fn new(data: &'a [u8]) -> Self {
+        DerReader {
+            data,
+            pos: 0
+        }
+    }
+
+    /// Returns true when the whole buffer has been read.
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    /// Returns the tag of the next element without consuming it.
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.get(self.pos).cloned()
+    }
+
+    // Reads a single byte of the buffer.
+    fn next_byte(&mut self) -> Result<u8, Error> {
+        let byte = *self.data.get(self.pos).ok_or(Error::Encoding("Unexpected end of DER data"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    /// Reads the next element checking it has the expected tag. Returns its content.
+    /// Only the minimal definite length form allowed by DER is accepted.
+    pub fn read_tlv(&mut self, tag: u8) -> Result<&'a [u8], Error> {
+        if self.next_byte()? != tag {return Err(Error::Encoding("Unexpected DER tag"))}
+        let first = self.next_byte()?;
+        let len = if first < 0x80 {
+            first as usize
+        } else {
+            let num_bytes = (first & 0x7f) as usize;
+            if num_bytes == 0 || num_bytes > std::mem::size_of::<usize>() {return Err(Error::Encoding("Invalid DER length"))}
+            let mut len = 0usize;
+            for _ in 0..num_bytes {
+                len = (len << 8) | self.next_byte()? as usize;
+            }
+            // Long form must only be used when needed and without leading zeros.
+            if len < 0x80 || len >> (8 * (num_bytes - 1)) == 0 {return Err(Error::Encoding("Non-minimal DER length"))}
+            len
+        };
+        if self.data.len() - self.pos < len {return Err(Error::Encoding("Unexpected end of DER data"))}
+        let content = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(content)
+    }
+
+    /// Reads a non-negative DER INTEGER.
+    pub fn read_integer(&mut self) -> Result<BigUint, Error> {
+        let content = self.read_tlv(TAG_INTEGER)?;
+        if content.is_empty() {return Err(Error::Encoding("Invalid DER integer"))}
+        if content[0] & 0x80 != 0 {return Err(Error::Unsupported("Negative DER integers aren't supported"))}
+        if content.len() > 1 && content[0] == 0 && content[1] & 0x80 == 0 {return Err(Error::Encoding("Non-minimal DER integer"))}
+        Ok(BigUint::from_bytes_be(content))
+    }
+
+    /// Reads a DER SEQUENCE returning a reader over its elements.
+    pub fn read_sequence(&mut self) -> Result<DerReader<'a>, Error> {
+        Ok(DerReader::new(self.read_tlv(TAG_SEQUENCE)?))
+    }
+
+    /// Reads an OBJECT IDENTIFIER returning the bytes of its encoded arcs.
+    pub fn read_oid(&mut self) -> Result<&'a [u8], Error> {
+        self.read_tlv(TAG_OID)
+    }
+
+    /// Reads a DER NULL.
+    pub fn read_null(&mut self) -> Result<(), Error> {
+        if !self.read_tlv(TAG_NULL)?.is_empty() {return Err(Error::Encoding("Invalid DER NULL"))}
+        Ok(())
+    }
+
+    /// Reads an OCTET STRING.
+    pub fn read_octet_string(&mut self) -> Result<&'a [u8], Error> {
+        self.read_tlv(TAG_OCTET_STRING)
+    }
+
+    /// Reads a BIT STRING that must not have unused bits.
+    pub fn read_bit_string(&mut self) -> Result<&'a [u8], Error> {
+        match self.read_tlv(TAG_BIT_STRING)?.split_first() {
+            Some((0, content)) => Ok(content),
+            _ => Err(Error::Encoding("Invalid DER BIT STRING"))
+        }
+    }
+
+    /// Reads an explicitly tagged context-specific element [tag_number] if it's the next one.
+    pub fn read_optional_explicit(&mut self, tag_number: u8) -> Result<Option<DerReader<'a>>, Error> {
+        if self.peek_tag() != Some(0xa0 | tag_number) {return Ok(None)}
+        Ok(Some(DerReader::new(self.read_tlv(0xa0 | tag_number)?)))
+    }
+
+    /// Fails if there's still data left to be read.
+    pub fn finish(&self) -> Result<(), Error> {
+        if !self.is_empty() {return Err(Error::Encoding("Trailing data after DER element"))}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn der_integers_and_lengths() {
+    assert_eq!(encode_integer(&BigUint::from(0u32)), vec![0x02, 0x01, 0x00]);
+    assert_eq!(encode_integer(&BigUint::from(127u32)), vec![0x02, 0x01, 0x7f]);
+    assert_eq!(encode_integer(&BigUint::from(128u32)), vec![0x02, 0x02, 0x00, 0x80]);
+    assert_eq!(encode_integer(&BigUint::from(65537u32)), vec![0x02, 0x03, 0x01, 0x00, 0x01]);
+    assert_eq!(&encode_tlv(TAG_OCTET_STRING, &[0u8; 200])[..3], &[0x04, 0x81, 0xc8]);
+    assert_eq!(&encode_tlv(TAG_OCTET_STRING, &[0u8; 300])[..4], &[0x04, 0x82, 0x01, 0x2c]);
+
+    let seq = encode_sequence(&[encode_integer(&BigUint::from(128u32)), encode_integer(&BigUint::from(3u32))]);
+    let mut reader = DerReader::new(&seq);
+    let mut inner = reader.read_sequence().unwrap();
+    assert!(reader.finish().is_ok());
+    assert_eq!(inner.read_integer().unwrap(), BigUint::from(128u32));
+    assert_eq!(inner.read_integer().unwrap(), BigUint::from(3u32));
+    assert!(inner.finish().is_ok());
+
+    // Negative, non-minimal and truncated encodings must be rejected.
+    assert!(DerReader::new(&[0x02, 0x01, 0x80]).read_integer().is_err());
+    assert!(DerReader::new(&[0x02, 0x02, 0x00, 0x01]).read_integer().is_err());
+    assert!(DerReader::new(&[0x02, 0x81, 0x01, 0x01]).read_integer().is_err());
+    assert!(DerReader::new(&[0x02, 0x80, 0x01, 0x00, 0x00]).read_integer().is_err());
+    assert!(DerReader::new(&[0x02, 0x03, 0x01]).read_integer().is_err());
+    assert!(DerReader::new(&[0x04, 0x01, 0x01]).read_integer().is_err());
+}
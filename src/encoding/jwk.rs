@@ -0,0 +1,292 @@
+//! JWK
+//! JSON Web Keys (RFC 7517, RFC 7518 §6.3), JWK Sets and RFC 7638 thumbprints.
+use num_bigint::BigUint;
+use serde_json::{Map, Value};
+use crate::error::Error;
+use crate::types::*;
+use crate::secret::Secret;
+use crate::hash::Hash;
+
+/// RSA key held by a JWK.
+#[derive(Clone, PartialEq)]
+pub enum JwkKey {
+    Public(PublicKey),
+    Secret(SecretKey)
+}
+
+/// JSON Web Key identified by its `kid`.
+#[derive(Clone, PartialEq)]
+pub struct Jwk {
+    pub kid: String,
+    /// Algorithm the key is meant for, like `RS256` or `RSA-OAEP-256` (RFC 7517 §4.4).
+    pub alg: Option<String>,
+    pub key: JwkKey
+}
+
+/// JWK Set, a `{"keys": [...]}` document holding several keys.
+#[derive(Clone, PartialEq, Default)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>
+}
+
+// Encodes an integer as Base64urlUInt: the minimal big-endian octets in unpadded base64url.
+fn encode_uint(x: &BigUint) -> Value {
+    Value::String(base64::encode_config(&x.to_bytes_be(), base64::URL_SAFE_NO_PAD))
+}
+
+// Decodes the Base64urlUInt member of a JWK, if present.
+fn decode_uint(jwk: &Map<String, Value>, member: &str) -> Result<Option<BigUint>, Error> {
+    match jwk.get(member) {
+        None => Ok(None),
+        Some(Value::String(b64)) => {
+            let bytes = base64::decode_config(b64, base64::URL_SAFE_NO_PAD).map_err(|_| Error::Encoding("Invalid base64url on JWK"))?;
+            if bytes.is_empty() {return Err(Error::Encoding("Empty integer on JWK"))}
+            Ok(Some(BigUint::from_bytes_be(&bytes)))
+        },
+        Some(_) => Err(Error::Encoding("JWK integers must be strings"))
+    }
+}
+
+// Decodes a required Base64urlUInt member of a JWK.
+fn require_uint(jwk: &Map<String, Value>, member: &str) -> Result<BigUint, Error> {
+    decode_uint(jwk, member)?.ok_or(Error::Encoding("Missing required member on JWK"))
+}
+
+// Parses a JSON object.
+fn parse_object(json: &str) -> Result<Map<String, Value>, Error> {
+    match serde_json::from_str(json) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(Error::Encoding("JWK must be a JSON object")),
+        Err(_) => Err(Error::Encoding("Invalid JSON"))
+    }
+}
+
+// SHA-256 of the RFC 7638 canonical JWK `{"e":...,"kty":"RSA","n":...}`, with the members in
+// lexicographic order and no whitespace.
+fn fingerprint(n: &BigUint, e: &BigUint) -> Vec<u8> {
+    let canonical = format!("{{\"e\":{},\"kty\":\"RSA\",\"n\":{}}}", encode_uint(e), encode_uint(n));
+    Hash::Sha256.digest(canonical.as_bytes())
+}
+
+impl PublicKey {
+    /// Returns the fingerprint of the Public Key: the SHA-256 of its RFC 7638 canonical JWK
+    /// `{"e":...,"kty":"RSA","n":...}`, with the members in lexicographic order and no whitespace.
+    pub fn fingerprint(&self) -> Vec<u8> {
+        fingerprint(&self.n, &self.e)
+    }
+
+    /// Returns the RFC 7638 JWK thumbprint of the Public Key, its fingerprint in unpadded base64url.
+    pub fn jwk_thumbprint(&self) -> String {
+        base64::encode_config(&self.fingerprint(), base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Encodes the Public Key as a JWK using its thumbprint as `kid`.
+    pub fn to_jwk(&self) -> String {
+        Jwk::from(self).to_json()
+    }
+
+    /// Decodes a Public Key from a JWK. The private members of the JWK, if any, are ignored.
+    pub fn from_jwk(json: &str) -> Result<Self, Error> {
+        Jwk::from_json(json)?.public_key()
+    }
+}
+
+impl SecretKey {
+    /// Returns the fingerprint of the Public Key of the Secret Key, which identifies it without revealing
+    /// anything secret. Keys that don't know e give the SHA-256 of the big-endian n instead.
+    pub fn fingerprint(&self) -> Vec<u8> {
+        match &self.e {
+            Some(e) => fingerprint(&self.n, e),
+            None => Hash::Sha256.digest(&self.n.to_bytes_be())
+        }
+    }
+
+    /// Returns the fingerprint of the Secret Key in unpadded base64url, the RFC 7638 JWK thumbprint of its
+    /// Public Key when the key knows e.
+    pub fn jwk_thumbprint(&self) -> String {
+        base64::encode_config(&self.fingerprint(), base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Encodes the Secret Key as a JWK using the thumbprint of its Public Key as `kid`.
+    /// The CRT members are included when the key knows them.
+    pub fn to_jwk(&self) -> Result<String, Error> {
+        Ok(Jwk::from_secret_key(self)?.to_json())
+    }
+
+    /// Decodes a Secret Key from a private JWK.
+    pub fn from_jwk(json: &str) -> Result<Self, Error> {
+        match Jwk::from_json(json)?.key {
+            JwkKey::Secret(sk) => Ok(sk),
+            JwkKey::Public(_) => Err(Error::InvalidKey("The JWK doesn't hold a private key"))
+        }
+    }
+}
+
+impl<'a> From<&'a PublicKey> for Jwk {
+    /// Builds a JWK for the Public Key identified by its thumbprint.
+    fn from(pk: &'a PublicKey) -> Self {
+        Jwk {
+            kid: pk.jwk_thumbprint(),
+            alg: None,
+            key: JwkKey::Public(pk.clone())
+        }
+    }
+}
+
+impl Jwk {
+    /// Builds a JWK for the Secret Key identified by the thumbprint of its Public Key.
+    /// The key must know its public exponent.
+    pub fn from_secret_key(sk: &SecretKey) -> Result<Self, Error> {
+        let e = sk.e.as_ref().ok_or(Error::InvalidKey("The Secret Key needs e to be encoded as a JWK"))?;
+        Ok(Jwk {
+            kid: PublicKey::new(&sk.n, e)?.jwk_thumbprint(),
+            alg: None,
+            key: JwkKey::Secret(sk.clone())
+        })
+    }
+
+    /// Returns the Public Key of the JWK.
+    pub fn public_key(&self) -> Result<PublicKey, Error> {
+        match &self.key {
+            JwkKey::Public(pk) => Ok(pk.clone()),
+            JwkKey::Secret(sk) => sk.to_public_key()
+        }
+    }
+
+    // Encodes the JWK members as a JSON object.
+    fn to_value(&self) -> Value {
+        let mut jwk = Map::new();
+        jwk.insert("kty".to_string(), Value::String("RSA".to_string()));
+        jwk.insert("kid".to_string(), Value::String(self.kid.clone()));
+        if let Some(alg) = &self.alg {
+            jwk.insert("alg".to_string(), Value::String(alg.clone()));
+        }
+        match &self.key {
+            JwkKey::Public(pk) => {
+                jwk.insert("n".to_string(), encode_uint(&pk.n));
+                jwk.insert("e".to_string(), encode_uint(&pk.e));
+            },
+            JwkKey::Secret(sk) => {
+                jwk.insert("n".to_string(), encode_uint(&sk.n));
+                if let Some(e) = &sk.e {
+                    jwk.insert("e".to_string(), encode_uint(e));
+                }
+                jwk.insert("d".to_string(), encode_uint(sk.d.expose_secret()));
+                if let Some(crt) = sk.crt.as_ref().map(Secret::expose_secret) {
+                    jwk.insert("p".to_string(), encode_uint(&crt.p));
+                    jwk.insert("q".to_string(), encode_uint(&crt.q));
+                    jwk.insert("dp".to_string(), encode_uint(&crt.dp));
+                    jwk.insert("dq".to_string(), encode_uint(&crt.dq));
+                    jwk.insert("qi".to_string(), encode_uint(&crt.qinv));
+                }
+            }
+        }
+        Value::Object(jwk)
+    }
+
+    // Decodes the JWK members of a JSON object. Unknown members are ignored.
+    fn from_value(jwk: &Map<String, Value>) -> Result<Self, Error> {
+        if jwk.get("kty").and_then(Value::as_str) != Some("RSA") {return Err(Error::Unsupported("Only JWKs with kty RSA are supported"))}
+        if jwk.contains_key("oth") {return Err(Error::Unsupported("Multi-prime JWKs aren't supported"))}
+        let n = require_uint(jwk, "n")?;
+        let e = require_uint(jwk, "e")?;
+        let pk = PublicKey::new(&n, &e)?;
+        let key = match decode_uint(jwk, "d")? {
+            None => JwkKey::Public(pk.clone()),
+            Some(d) => {
+                let sk = match (decode_uint(jwk, "p")?, decode_uint(jwk, "q")?) {
+                    (Some(p), Some(q)) => {
+                        let sk = SecretKey::new_with_primes(&n, &e, &d, &p, &q)?;
+                        // The CRT members are optional but must match the primes when present.
+                        if let Some(crt) = sk.crt.as_ref().map(Secret::expose_secret) {
+                            for (member, value) in &[("dp", &crt.dp), ("dq", &crt.dq), ("qi", &crt.qinv)] {
+                                if decode_uint(jwk, member)?.is_some_and(|x| x != **value) {
+                                    return Err(Error::InvalidKey("Inconsistent CRT components on JWK"))
+                                }
+                            }
+                        }
+                        sk
+                    },
+                    (None, None) => SecretKey::new_with_e(&n, &e, &d)?,
+                    _ => return Err(Error::Encoding("JWK must have both p and q or none of them"))
+                };
+                JwkKey::Secret(sk)
+            }
+        };
+        let kid = match jwk.get("kid") {
+            None => pk.jwk_thumbprint(),
+            Some(Value::String(kid)) => kid.clone(),
+            Some(_) => return Err(Error::Encoding("JWK kid must be a string"))
+        };
+        let alg = match jwk.get("alg") {
+            None => None,
+            Some(Value::String(alg)) => Some(alg.clone()),
+            Some(_) => return Err(Error::Encoding("JWK alg must be a string"))
+        };
+        Ok(Jwk {
+            kid,
+            alg,
+            key
+        })
+    }
+
+    /// Serializes the JWK as JSON.
+    pub fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+
+    /// Parses a JWK from JSON. When there's no `kid` the thumbprint of the key is used.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        Jwk::from_value(&parse_object(json)?)
+    }
+}
+
+impl JwkSet {
+    /// Creates a JWK Set holding the given keys.
+    pub fn new(keys: Vec<Jwk>) -> Self {
+        JwkSet {
+            keys
+        }
+    }
+
+    /// Returns the first key of the set with the given `kid`.
+    pub fn find(&self, kid: &str) -> Option<&Jwk> {
+        self.keys.iter().find(|jwk| jwk.kid == kid)
+    }
+
+    /// Serializes the JWK Set as JSON.
+    pub fn to_json(&self) -> String {
+        let keys = self.keys.iter().map(Jwk::to_value).collect();
+        let mut set = Map::new();
+        set.insert("keys".to_string(), Value::Array(keys));
+        Value::Object(set).to_string()
+    }
+
+    /// Parses a JWK Set from JSON. Keys that aren't RSA keys are skipped, as RFC 7517 §5 asks for.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let set = parse_object(json)?;
+        let keys = match set.get("keys") {
+            Some(Value::Array(keys)) => keys,
+            _ => return Err(Error::Encoding("JWK Set must have a keys array"))
+        };
+        let mut res = vec!();
+        for jwk in keys {
+            let jwk = jwk.as_object().ok_or(Error::Encoding("JWK must be a JSON object"))?;
+            if jwk.get("kty").and_then(Value::as_str) != Some("RSA") {continue}
+            res.push(Jwk::from_value(jwk)?);
+        }
+        Ok(JwkSet::new(res))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn jwk_thumbprint_rfc7638() {
+    // Example key of RFC 7638 §3.1.
+    let n = "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw";
+    let pk = PublicKey::new(
+        &BigUint::from_bytes_be(&base64::decode_config(n, base64::URL_SAFE_NO_PAD).unwrap()),
+        &BigUint::from(65537u32)
+    ).unwrap();
+    assert_eq!(pk.jwk_thumbprint(), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+}
@@ -0,0 +1,7 @@
+pub mod der;
+pub mod pem;
+pub mod pkcs1;
+pub mod pkcs8;
+pub mod pbes2;
+pub mod ssh;
+pub mod jwk;
@@ -0,0 +1,289 @@
+//! PBES2
+//! Password-based encryption of PKCS#8 keys as EncryptedPrivateKeyInfo (RFC 5958 and RFC 8018 §6.2).
+use rand::Rng;
+use num_bigint::BigUint;
+use aes::Aes256;
+use aes::cipher::{KeyIvInit, BlockEncryptMut, BlockDecryptMut, block_padding::Pkcs7};
+use aes_gcm::{Aes256Gcm, KeyInit};
+use aes_gcm::aead::{Aead, Payload};
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use crate::error::Error;
+use crate::hash::Hash;
+use crate::encoding::der::*;
+
+pub const ENCRYPTED_PRIVATE_KEY_PEM_LABEL: &str = "ENCRYPTED PRIVATE KEY";
+
+/// PBES2 OID (1.2.840.113549.1.5.13)
+pub const PBES2_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d];
+/// PBKDF2 OID (1.2.840.113549.1.5.12)
+pub const PBKDF2_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0c];
+/// id-scrypt OID (1.3.6.1.4.1.11591.4.11)
+pub const SCRYPT_OID: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x04, 0x0b];
+/// aes256-CBC OID (2.16.840.1.101.3.4.1.42)
+pub const AES_256_CBC_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+/// id-aes256-GCM OID (2.16.840.1.101.3.4.1.46)
+pub const AES_256_GCM_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2e];
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const GCM_NONCE_LEN: usize = 12;
+const GCM_TAG_LEN: usize = 16;
+
+/// Highest PBKDF2 iteration count accepted, so a crafted key can't keep the CPU busy for hours.
+pub const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+/// Most memory scrypt may use, 128·r·N bytes for its working buffer plus 128·r·p, as OpenSSL's default maxmem.
+pub const MAX_SCRYPT_MEMORY: u128 = 32 * 1024 * 1024;
+
+/// Key derivation function used to turn the password into the encryption key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kdf {
+    /// PBKDF2 (RFC 8018 §5.2) using HMAC with the given hash as PRF.
+    Pbkdf2 { prf: Hash, iterations: u32 },
+    /// scrypt (RFC 7914) with cost N = 2^log_n, block size r and parallelization p.
+    Scrypt { log_n: u8, r: u32, p: u32 }
+}
+
+/// Cipher used to encrypt the PrivateKeyInfo with the derived key.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cipher {
+    Aes256Cbc,
+    Aes256Gcm
+}
+
+/// Parameters of the PBES2 encryption scheme.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pbes2Params {
+    pub kdf: Kdf,
+    pub cipher: Cipher
+}
+
+impl Default for Pbes2Params {
+    /// PBKDF2-HMAC-SHA256 with 600.000 iterations and AES-256-CBC.
+    fn default() -> Self {
+        Pbes2Params {
+            kdf: Kdf::Pbkdf2 { prf: Hash::Sha256, iterations: 600_000 },
+            cipher: Cipher::Aes256Cbc
+        }
+    }
+}
+
+impl Pbes2Params {
+    /// Creates PBES2 params with the selected key derivation function and cipher.
+    pub fn new(kdf: Kdf, cipher: Cipher) -> Self {
+        Pbes2Params {
+            kdf,
+            cipher
+        }
+    }
+}
+
+// Returns the encoded arcs of the hmacWithSHA* OID (1.2.840.113549.2.x) used as PBKDF2 PRF.
+fn hmac_oid(hash: Hash) -> &'static [u8] {
+    match hash {
+        Hash::Sha1 => &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x07],
+        Hash::Sha256 => &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x09],
+        Hash::Sha384 => &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x0a],
+        Hash::Sha512 => &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x0b]
+    }
+}
+
+// Derives the encryption key from the password.
+fn derive_key(password: &[u8], salt: &[u8], kdf: &Kdf) -> Result<Vec<u8>, Error> {
+    let mut key = vec![0u8; KEY_LEN];
+    match *kdf {
+        Kdf::Pbkdf2 { prf, iterations } => {
+            if iterations == 0 {return Err(Error::InvalidParams("PBKDF2 iteration count must be positive"))}
+            if iterations > MAX_PBKDF2_ITERATIONS {return Err(Error::InvalidParams("PBKDF2 iteration count is too high"))}
+            match prf {
+                Hash::Sha1 => pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, iterations, &mut key),
+                Hash::Sha256 => pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut key),
+                Hash::Sha384 => pbkdf2::pbkdf2_hmac::<Sha384>(password, salt, iterations, &mut key),
+                Hash::Sha512 => pbkdf2::pbkdf2_hmac::<Sha512>(password, salt, iterations, &mut key)
+            }
+        },
+        Kdf::Scrypt { log_n, r, p } => {
+            // The parameters may come from an untrusted key, so the memory is checked before scrypt allocates it.
+            if log_n >= 64 || 128 * r as u128 * ((1u128 << log_n) + p as u128) > MAX_SCRYPT_MEMORY {
+                return Err(Error::InvalidParams("scrypt parameters need too much memory"))
+            }
+            let params = scrypt::Params::new(log_n, r, p, KEY_LEN).map_err(|_| Error::InvalidParams("Invalid scrypt parameters"))?;
+            scrypt::scrypt(password, salt, &params, &mut key).map_err(|_| Error::InvalidParams("Invalid scrypt parameters"))?;
+        }
+    }
+    Ok(key)
+}
+
+// Reads a small non-negative INTEGER as u64.
+fn read_u64(reader: &mut DerReader) -> Result<u64, Error> {
+    let x = reader.read_integer()?;
+    if x.bits() > 64 {return Err(Error::Encoding("Integer too large"))}
+    Ok(x.to_bytes_be().iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+}
+
+// Encodes the keyDerivationFunc AlgorithmIdentifier.
+fn encode_kdf(kdf: &Kdf, salt: &[u8]) -> Vec<u8> {
+    match *kdf {
+        Kdf::Pbkdf2 { prf, iterations } => {
+            let mut params = vec![encode_tlv(TAG_OCTET_STRING, salt), encode_integer(&BigUint::from(iterations))];
+            // hmacWithSHA1 is the default PRF so it's omitted.
+            if prf != Hash::Sha1 {
+                params.push(encode_sequence(&[encode_oid(hmac_oid(prf)), encode_null()]));
+            }
+            encode_sequence(&[encode_oid(PBKDF2_OID), encode_sequence(&params)])
+        },
+        Kdf::Scrypt { log_n, r, p } => {
+            encode_sequence(&[encode_oid(SCRYPT_OID), encode_sequence(&[
+                encode_tlv(TAG_OCTET_STRING, salt),
+                encode_integer(&BigUint::from(1u64 << log_n)),
+                encode_integer(&BigUint::from(r)),
+                encode_integer(&BigUint::from(p))
+            ])])
+        }
+    }
+}
+
+// Decodes the keyDerivationFunc AlgorithmIdentifier returning the KDF and its salt.
+fn decode_kdf<'a>(reader: &mut DerReader<'a>) -> Result<(Kdf, &'a [u8]), Error> {
+    let mut seq = reader.read_sequence()?;
+    let oid = seq.read_oid()?;
+    let mut params = seq.read_sequence()?;
+    seq.finish()?;
+    let salt = params.read_octet_string()?;
+    let kdf = if oid == PBKDF2_OID {
+        let iterations = read_u64(&mut params)?;
+        if iterations == 0 || iterations > u32::MAX as u64 {return Err(Error::InvalidParams("Invalid PBKDF2 iteration count"))}
+        // Optional keyLength, which must match the key size of the cipher.
+        if params.peek_tag() == Some(TAG_INTEGER) && read_u64(&mut params)? != KEY_LEN as u64 {
+            return Err(Error::Unsupported("Unsupported PBKDF2 key length"))
+        }
+        let prf = if params.is_empty() {
+            Hash::Sha1
+        } else {
+            let mut prf = params.read_sequence()?;
+            let prf_oid = prf.read_oid()?;
+            if !prf.is_empty() {prf.read_null()?}
+            prf.finish()?;
+            *[Hash::Sha1, Hash::Sha256, Hash::Sha384, Hash::Sha512].iter()
+                .find(|hash| hmac_oid(**hash) == prf_oid)
+                .ok_or(Error::Unsupported("Unsupported PBKDF2 PRF"))?
+        };
+        Kdf::Pbkdf2 { prf, iterations: iterations as u32 }
+    } else if oid == SCRYPT_OID {
+        let n = read_u64(&mut params)?;
+        if !n.is_power_of_two() || n < 2 {return Err(Error::InvalidParams("Invalid scrypt cost parameter"))}
+        let r = read_u64(&mut params)?;
+        let p = read_u64(&mut params)?;
+        if r > u32::MAX as u64 || p > u32::MAX as u64 {return Err(Error::InvalidParams("Invalid scrypt parameters"))}
+        if !params.is_empty() && read_u64(&mut params)? != KEY_LEN as u64 {
+            return Err(Error::Unsupported("Unsupported scrypt key length"))
+        }
+        Kdf::Scrypt { log_n: n.trailing_zeros() as u8, r: r as u32, p: p as u32 }
+    } else {
+        return Err(Error::Unsupported("Unsupported key derivation function"))
+    };
+    params.finish()?;
+    Ok((kdf, salt))
+}
+
+/// Encrypts a DER PrivateKeyInfo with the password returning a DER EncryptedPrivateKeyInfo.
+pub fn encrypt(private_key_info: &[u8], password: &[u8], params: &Pbes2Params) -> Result<Vec<u8>, Error> {
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let key = derive_key(password, &salt, &params.kdf)?;
+
+    let (cipher_alg, encrypted) = match params.cipher {
+        Cipher::Aes256Cbc => {
+            let mut iv = [0u8; 16];
+            rng.fill_bytes(&mut iv);
+            let encrypted = cbc::Encryptor::<Aes256>::new(key[..].into(), (&iv).into())
+                .encrypt_padded_vec_mut::<Pkcs7>(private_key_info);
+            (encode_sequence(&[encode_oid(AES_256_CBC_OID), encode_tlv(TAG_OCTET_STRING, &iv)]), encrypted)
+        },
+        Cipher::Aes256Gcm => {
+            let mut nonce = [0u8; GCM_NONCE_LEN];
+            rng.fill_bytes(&mut nonce);
+            let encrypted = Aes256Gcm::new(key[..].into())
+                .encrypt((&nonce).into(), Payload { msg: private_key_info, aad: &[] })
+                .map_err(|_| Error::MessageTooLong)?;
+            // GCMParameters ::= SEQUENCE { aes-nonce, aes-ICVlen } (RFC 5084)
+            let gcm_params = encode_sequence(&[encode_tlv(TAG_OCTET_STRING, &nonce), encode_integer(&BigUint::from(GCM_TAG_LEN))]);
+            (encode_sequence(&[encode_oid(AES_256_GCM_OID), gcm_params]), encrypted)
+        }
+    };
+    let pbes2 = encode_sequence(&[encode_oid(PBES2_OID), encode_sequence(&[encode_kdf(&params.kdf, &salt), cipher_alg])]);
+    Ok(encode_sequence(&[pbes2, encode_tlv(TAG_OCTET_STRING, &encrypted)]))
+}
+
+/// Decrypts a DER EncryptedPrivateKeyInfo with the password returning the DER PrivateKeyInfo.
+/// A wrong password gives back a decryption error.
+pub fn decrypt(encrypted_private_key_info: &[u8], password: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut reader = DerReader::new(encrypted_private_key_info);
+    let mut seq = reader.read_sequence()?;
+    reader.finish()?;
+    let mut alg = seq.read_sequence()?;
+    let encrypted = seq.read_octet_string()?;
+    seq.finish()?;
+    if alg.read_oid()? != PBES2_OID {return Err(Error::Unsupported("Only PBES2 encrypted keys are supported"))}
+    let mut pbes2 = alg.read_sequence()?;
+    alg.finish()?;
+    let (kdf, salt) = decode_kdf(&mut pbes2)?;
+    let mut cipher_alg = pbes2.read_sequence()?;
+    pbes2.finish()?;
+
+    let cipher_oid = cipher_alg.read_oid()?;
+    if cipher_oid == AES_256_CBC_OID {
+        let iv = cipher_alg.read_octet_string()?;
+        cipher_alg.finish()?;
+        if iv.len() != 16 {return Err(Error::Encoding("Invalid AES-CBC IV"))}
+        let key = derive_key(password, salt, &kdf)?;
+        cbc::Decryptor::<Aes256>::new(key[..].into(), iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(encrypted)
+            .map_err(|_| Error::Decryption)
+    } else if cipher_oid == AES_256_GCM_OID {
+        let mut gcm_params = cipher_alg.read_sequence()?;
+        cipher_alg.finish()?;
+        let nonce = gcm_params.read_octet_string()?;
+        let tag_len = if gcm_params.is_empty() {12} else {read_u64(&mut gcm_params)?};
+        gcm_params.finish()?;
+        if nonce.len() != GCM_NONCE_LEN || tag_len != GCM_TAG_LEN as u64 {return Err(Error::Unsupported("Unsupported AES-GCM parameters"))}
+        let key = derive_key(password, salt, &kdf)?;
+        Aes256Gcm::new(key[..].into())
+            .decrypt(nonce.into(), Payload { msg: encrypted, aad: &[] })
+            .map_err(|_| Error::Decryption)
+    } else {
+        Err(Error::Unsupported("Unsupported PBES2 cipher"))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn pbes2_roundtrip() {
+    let data = b"PrivateKeyInfo goes here";
+    let kdfs = [Kdf::Pbkdf2 { prf: Hash::Sha256, iterations: 10 }, Kdf::Pbkdf2 { prf: Hash::Sha1, iterations: 10 }, Kdf::Scrypt { log_n: 4, r: 8, p: 1 }];
+    for kdf in kdfs.iter() {
+        for cipher in [Cipher::Aes256Cbc, Cipher::Aes256Gcm].iter() {
+            let params = Pbes2Params::new(*kdf, *cipher);
+            let encrypted = encrypt(data, b"password", &params).unwrap();
+            assert_eq!(decrypt(&encrypted, b"password").unwrap(), data.to_vec());
+            assert!(decrypt(&encrypted, b"wrong password").map(|res| res != data.to_vec()).unwrap_or(true));
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn pbes2_caps_kdf_costs() {
+    let data = b"PrivateKeyInfo goes here";
+    let expensive = [
+        Kdf::Pbkdf2 { prf: Hash::Sha256, iterations: MAX_PBKDF2_ITERATIONS + 1 },
+        Kdf::Scrypt { log_n: 20, r: 8, p: 1 },
+        Kdf::Scrypt { log_n: 4, r: 8, p: u32::MAX },
+        Kdf::Scrypt { log_n: 70, r: 1, p: 1 }
+    ];
+    for kdf in expensive.iter() {
+        let params = Pbes2Params::new(*kdf, Cipher::Aes256Cbc);
+        assert!(matches!(encrypt(data, b"password", &params), Err(Error::InvalidParams(_))));
+    }
+}
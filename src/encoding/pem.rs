@@ -0,0 +1,55 @@
+//! PEM
+use crate::error::Error;
+
+/// Wraps DER data on a PEM armour (RFC 7468) with the given label, 64 characters per line.
+pub fn encode(label: &str, der: &[u8]) -> String {
+    encode_with_width(label, der, 64)
+}
+
+/// Wraps data on a PEM armour with the given label and number of characters per line.
+pub fn encode_with_width(label: &str, der: &[u8], width: usize) -> String {
+    let b64 = base64::encode(der);
+    let mut res = format!("-----BEGIN {}-----\n", label);
+    for chunk in b64.as_bytes().chunks(width) {
+        res.push_str(std::str::from_utf8(chunk).unwrap());
+        res.push('\n');
+    }
+    res.push_str(&format!("-----END {}-----\n", label));
+    res
+}
+
+/// Removes the PEM armour with the given label returning the DER data.
+/// Text before and after the armour and blank spaces between lines are ignored.
+pub fn decode(label: &str, pem: &str) -> Result<Vec<u8>, Error> {
+    let (found, der) = decode_any(pem)?;
+    if found != label {return Err(Error::Encoding("Unexpected PEM label"))}
+    Ok(der)
+}
+
+/// Removes the PEM armour returning its label and the DER data.
+pub fn decode_any(pem: &str) -> Result<(String, Vec<u8>), Error> {
+    let begin = pem.find("-----BEGIN ").ok_or(Error::Encoding("PEM header not found"))?;
+    let rest = &pem[begin + 11..];
+    let label_end = rest.find("-----").ok_or(Error::Encoding("Malformed PEM header"))?;
+    let label = &rest[..label_end];
+    let body = &rest[label_end + 5..];
+    let footer = format!("-----END {}-----", label);
+    let end = body.find(&footer).ok_or(Error::Encoding("PEM footer not found"))?;
+    let b64: String = body[..end].chars().filter(|c| !c.is_whitespace()).collect();
+    let der = base64::decode(&b64).map_err(|_| Error::Encoding("Invalid base64 on PEM body"))?;
+    Ok((label.to_string(), der))
+}
+
+#[cfg(test)]
+#[test]
+fn pem_roundtrip() {
+    let der: Vec<u8> = (0..100u8).collect();
+    let pem = encode("TEST DATA", &der);
+    assert!(pem.starts_with("-----BEGIN TEST DATA-----\n"));
+    assert!(pem.ends_with("-----END TEST DATA-----\n"));
+    assert!(pem.lines().all(|l| l.len() <= 64));
+    assert_eq!(decode("TEST DATA", &pem).unwrap(), der);
+    assert_eq!(decode("TEST DATA", &format!("junk\r\n{}", pem.replace('\n', "\r\n"))).unwrap(), der);
+    assert!(decode("OTHER DATA", &pem).is_err());
+    assert!(decode("TEST DATA", &pem.replace("-----END", "-----FOO")).is_err());
+}
@@ -0,0 +1,60 @@
+//! Error
+//! Error type returned by every fallible operation of the crate.
+use std::error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file or stream failed.
+    Io(io::Error),
+    /// Malformed DER, PEM, OpenSSH, JWK, JOSE, hex, base64 or envelope data.
+    Encoding(&'static str),
+    /// Well-formed data using an algorithm, version or feature that isn't supported.
+    Unsupported(&'static str),
+    /// Parameters out of their valid range, like an iteration count or a chunk size.
+    InvalidParams(&'static str),
+    /// The key doesn't have the properties or components the operation needs.
+    InvalidKey(&'static str),
+    /// The key pair couldn't be generated with the requested params.
+    KeyGeneration(&'static str),
+    /// The message doesn't fit on the modulus with the selected padding.
+    MessageTooLong,
+    /// RSA decryption failed. Wrong length, range and padding give back the same error (RFC 8017 §7.1.2).
+    InvalidPadding,
+    /// Authenticated decryption failed: a wrong key, password or modified data.
+    Decryption,
+    /// The signature doesn't match the message and the Public Key.
+    Verification,
+    /// A signature didn't verify before being released, so a fault happened while computing it.
+    Fault
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Encoding(msg) | Error::Unsupported(msg) | Error::InvalidParams(msg)
+                | Error::InvalidKey(msg) | Error::KeyGeneration(msg) => write!(f, "{}", msg),
+            Error::MessageTooLong => write!(f, "Message too long for the key size and padding"),
+            Error::InvalidPadding | Error::Decryption => write!(f, "Decryption error"),
+            Error::Verification => write!(f, "Invalid signature"),
+            Error::Fault => write!(f, "Fault detected while signing")
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
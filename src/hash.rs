@@ -0,0 +1,93 @@
+//! Hash
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512, Digest};
+use crate::error::Error;
+
+/// Hash functions that can be used by the padding and signature schemes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hash {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512
+}
+
+impl Default for Hash {
+    /// SHA-256 is the default hash function.
+    fn default() -> Self {
+        Hash::Sha256
+    }
+}
+
+impl Hash {
+    /// Hashes the data passed on the params returning the digest bytes.
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Hash::Sha1 => Sha1::digest(data).to_vec(),
+            Hash::Sha256 => Sha256::digest(data).to_vec(),
+            Hash::Sha384 => Sha384::digest(data).to_vec(),
+            Hash::Sha512 => Sha512::digest(data).to_vec()
+        }
+    }
+
+    /// Returns the length in bytes of the digests produced by the hash function.
+    pub fn output_len(self) -> usize {
+        match self {
+            Hash::Sha1 => 20,
+            Hash::Sha256 => 32,
+            Hash::Sha384 => 48,
+            Hash::Sha512 => 64
+        }
+    }
+
+    /// Returns the encoded arcs of the hash function OBJECT IDENTIFIER.
+    pub fn oid(self) -> &'static [u8] {
+        match self {
+            Hash::Sha1 => &[0x2b, 0x0e, 0x03, 0x02, 0x1a],
+            Hash::Sha256 => &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01],
+            Hash::Sha384 => &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02],
+            Hash::Sha512 => &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03]
+        }
+    }
+
+    /// Gets the hash function identified by the encoded arcs of an OBJECT IDENTIFIER.
+    pub fn from_oid(oid: &[u8]) -> Result<Self, Error> {
+        [Hash::Sha1, Hash::Sha256, Hash::Sha384, Hash::Sha512].iter()
+            .find(|hash| hash.oid() == oid)
+            .cloned()
+            .ok_or(Error::Unsupported("Unsupported hash function OID"))
+    }
+
+    /// Returns the DER encoding of the DigestInfo ASN.1 structure (RFC 8017 §9.2) without the digest,
+    /// which must be appended right after it.
+    pub fn digest_info_prefix(self) -> &'static [u8] {
+        match self {
+            Hash::Sha1 => &[0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14],
+            Hash::Sha256 => &[0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20],
+            Hash::Sha384 => &[0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02, 0x05, 0x00, 0x04, 0x30],
+            Hash::Sha512 => &[0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03, 0x05, 0x00, 0x04, 0x40]
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn digests_have_expected_len() {
+    for hash in &[Hash::Sha1, Hash::Sha256, Hash::Sha384, Hash::Sha512] {
+        assert_eq!(hash.digest(b"abc").len(), hash.output_len());
+    }
+    assert_eq!(Hash::Sha256.digest(b"abc")[..4], [0xba, 0x78, 0x16, 0xbf]);
+}
+
+#[cfg(test)]
+#[test]
+fn digest_info_prefixes_declare_digest_len() {
+    for hash in &[Hash::Sha1, Hash::Sha256, Hash::Sha384, Hash::Sha512] {
+        let prefix = hash.digest_info_prefix();
+        // Outer SEQUENCE length covers the prefix and the digest, last byte is the OCTET STRING length.
+        assert_eq!(&prefix[6..6 + hash.oid().len()], hash.oid());
+        assert_eq!(Hash::from_oid(hash.oid()).unwrap(), *hash);
+        assert_eq!(prefix[1] as usize, prefix.len() - 2 + hash.output_len());
+        assert_eq!(prefix[prefix.len() - 1] as usize, hash.output_len());
+    }
+}
@@ -0,0 +1,141 @@
+//! Blinding
+//! RSA blinding (P. Kocher, "Timing Attacks on Implementations of Diffie-Hellman, RSA, DSS, and Other Systems" §10):
+//! c is multiplied by r^e before the private exponentiation and the result by r^(-1) afterwards, so the
+//! exponentiation never works on a value known by an attacker. The factors are squared after every use and
+//! regenerated from a new random r every `REFRESH_USES` uses.
+use num_bigint::{BigUint, RandBigInt};
+use num::One;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use crate::helpers::math::{mod_exp_pow, mod_inverse};
+use crate::secret::Wipe;
+
+/// Uses of the factors of a random r before a new one is generated.
+pub const REFRESH_USES: u32 = 32;
+
+// Blinding factors r^e mod n and r^(-1) mod n of the key they were generated for.
+struct Factors {
+    n: BigUint,
+    e: BigUint,
+    blind: BigUint,
+    unblind: BigUint,
+    uses: u32
+}
+
+impl Factors {
+    fn new(n: &BigUint, e: &BigUint) -> Self {
+        let mut rng = rand::thread_rng();
+        loop {
+            let r = rng.gen_biguint_below(n);
+            if let Some(unblind) = mod_inverse(&r, n) {
+                return Factors {
+                    n: n.clone(),
+                    e: e.clone(),
+                    blind: mod_exp_pow(&r, e, n),
+                    unblind,
+                    uses: 0
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Factors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Factors").field("uses", &self.uses).finish_non_exhaustive()
+    }
+}
+
+// The factors would unblind the values of the exponentiation, so they're secret too.
+impl Drop for Factors {
+    fn drop(&mut self) {
+        self.blind.wipe();
+        self.unblind.wipe();
+    }
+}
+
+/// Blinding state of a Secret Key: whether it's enabled and the cached factors, shared with the clones of the key.
+#[derive(Clone, Debug)]
+pub struct Blinding {
+    enabled: bool,
+    factors: Arc<Mutex<Option<Factors>>>
+}
+
+impl Default for Blinding {
+    /// Creates an enabled blinding without factors, they're generated on first use.
+    fn default() -> Self {
+        Blinding {
+            enabled: true,
+            factors: Arc::new(Mutex::new(None))
+        }
+    }
+}
+
+/// Blinding doesn't change the results of a key, so it never makes two keys different.
+impl PartialEq for Blinding {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Blinding {
+    /// Returns whether the private operations are blinded.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enables or disables blinding.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    // Returns the factors to use and squares the cached ones for the next use. Concurrent users of the
+    // same key never get the same factors.
+    fn next_factors(&self, n: &BigUint, e: &BigUint) -> (BigUint, BigUint) {
+        let mut cached = self.factors.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut factors = match cached.take() {
+            Some(factors) if &factors.n == n && &factors.e == e && factors.uses < REFRESH_USES => factors,
+            _ => Factors::new(n, e)
+        };
+        let (blind, unblind) = ((&factors.blind * &factors.blind) % n, (&factors.unblind * &factors.unblind) % n);
+        let res = (std::mem::replace(&mut factors.blind, blind), std::mem::replace(&mut factors.unblind, unblind));
+        factors.uses += 1;
+        *cached = Some(factors);
+        res
+    }
+
+    /// Runs the private operation `op` over c blinded with the factors of n and e and unblinds its result.
+    /// Without e, with a modulus smaller than 2 or when it's disabled, `op` runs over c directly.
+    pub fn apply<F: FnOnce(&BigUint) -> BigUint>(&self, c: &BigUint, n: &BigUint, e: Option<&BigUint>, op: F) -> BigUint {
+        let e = match e {
+            Some(e) if self.enabled && n > &BigUint::one() => e,
+            _ => return op(c)
+        };
+        let (blind, unblind) = self.next_factors(n, e);
+        let m = op(&((c * blind) % n));
+        (m * unblind) % n
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn blinding_factors_are_refreshed() {
+    // n = 3233, e = 17, d = 2753
+    let (n, e, d) = (BigUint::from(3233u32), BigUint::from(17u32), BigUint::from(2753u32));
+    let blinding = Blinding::default();
+    for _ in 0..2 * REFRESH_USES + 1 {
+        let (blind, unblind) = blinding.next_factors(&n, &e);
+        // r^e * (r^(-1))^e = 1
+        assert!((blind * mod_exp_pow(&unblind, &e, &n) % &n).is_one());
+    }
+    assert!(blinding.factors.lock().unwrap().as_ref().unwrap().uses == 1);
+
+    for c in 0..3233u32 {
+        let c = BigUint::from(c);
+        assert_eq!(blinding.apply(&c, &n, Some(&e), |c| mod_exp_pow(c, &d, &n)), mod_exp_pow(&c, &d, &n));
+    }
+    // A different key regenerates the factors.
+    let (n2, e2) = (BigUint::from(3127u32), BigUint::from(3u32));
+    let (blind, unblind) = blinding.clone().next_factors(&n2, &e2);
+    assert!((blind * mod_exp_pow(&unblind, &e2, &n2) % &n2).is_one());
+}
@@ -0,0 +1,181 @@
+//! Constant time
+//! Fixed-width arithmetic on little-endian 64-bit limbs for the operations on secret values. Loops only
+//! depend on the number of limbs and values are selected with masks instead of branches, so the running
+//! time and the memory accesses don't depend on the values.
+use num_bigint::BigUint;
+use zeroize::Zeroize;
+
+/// Operations of the backend. Test builds record them to check they don't depend on the data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    MontMul,
+    Lookup,
+    ModAdd,
+    ModSub,
+    Mul
+}
+
+#[cfg(test)]
+thread_local! {
+    static TRACE: std::cell::RefCell<Vec<Op>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+#[cfg(test)]
+pub fn record(op: Op) {
+    TRACE.with(|trace| trace.borrow_mut().push(op));
+}
+
+#[cfg(not(test))]
+#[inline(always)]
+pub fn record(_: Op) {}
+
+/// Runs f returning its result and the operations of the backend it went through.
+#[cfg(test)]
+pub fn trace<R, F: FnOnce() -> R>(f: F) -> (R, Vec<Op>) {
+    TRACE.with(|trace| trace.borrow_mut().clear());
+    let res = f();
+    (res, TRACE.with(|trace| trace.borrow_mut().split_off(0)))
+}
+
+/// Splits x on `len` little-endian limbs. Only the length of x affects the conversion time.
+pub fn to_limbs(x: &BigUint, len: usize) -> Vec<u64> {
+    let mut limbs = vec![0u64; len];
+    let mut bytes = x.to_bytes_le();
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks(8)) {
+        let mut buf = [0u8; 8];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        *limb = u64::from_le_bytes(buf);
+        buf.zeroize();
+    }
+    // x may be secret, like the exponents.
+    bytes.zeroize();
+    limbs
+}
+
+pub fn from_limbs(limbs: &[u64]) -> BigUint {
+    let bytes: Vec<u8> = limbs.iter().flat_map(|limb| limb.to_le_bytes()).collect();
+    BigUint::from_bytes_le(&bytes)
+}
+
+/// Returns all ones when a == b and zero otherwise.
+pub fn eq_mask(a: u64, b: u64) -> u64 {
+    let x = a ^ b;
+    // The top bit of x | -x is set for any x but zero.
+    ((x | x.wrapping_neg()) >> 63).wrapping_sub(1)
+}
+
+/// Returns whether a and b, of the same limbs, are equal. Every limb is compared whatever the values.
+pub fn eq(a: &[u64], b: &[u64]) -> bool {
+    a.iter().zip(b).fold(u64::MAX, |mask, (a, b)| mask & eq_mask(*a, *b)) == u64::MAX
+}
+
+/// Returns all ones when bit is 1 and zero when it's 0.
+pub fn bit_mask(bit: u64) -> u64 {
+    bit.wrapping_neg()
+}
+
+/// Assigns `a` to `out` when mask is all ones and leaves it untouched when it's zero.
+pub fn cond_assign(mask: u64, out: &mut [u64], a: &[u64]) {
+    for (o, a) in out.iter_mut().zip(a) {
+        *o = (a & mask) | (*o & !mask);
+    }
+}
+
+/// Copies table[index] into `out` reading every entry of the table.
+pub fn lookup(table: &[Vec<u64>], index: u64, out: &mut [u64]) {
+    record(Op::Lookup);
+    out.iter_mut().for_each(|o| *o = 0);
+    for (i, entry) in table.iter().enumerate() {
+        let mask = eq_mask(i as u64, index);
+        for (o, e) in out.iter_mut().zip(entry) {
+            *o |= e & mask;
+        }
+    }
+}
+
+/// out = a + b returning the carry. All of them have the same limbs.
+pub fn add(a: &[u64], b: &[u64], out: &mut [u64]) -> u64 {
+    let mut carry = 0u64;
+    for ((o, a), b) in out.iter_mut().zip(a).zip(b) {
+        let sum = *a as u128 + *b as u128 + carry as u128;
+        *o = sum as u64;
+        carry = (sum >> 64) as u64;
+    }
+    carry
+}
+
+/// out = a - b returning the borrow. All of them have the same limbs.
+pub fn sub(a: &[u64], b: &[u64], out: &mut [u64]) -> u64 {
+    let mut borrow = 0u64;
+    for ((o, a), b) in out.iter_mut().zip(a).zip(b) {
+        let diff = (*a as u128).wrapping_sub(*b as u128).wrapping_sub(borrow as u128);
+        *o = diff as u64;
+        borrow = ((diff >> 64) as u64) & 1;
+    }
+    borrow
+}
+
+/// out = a * b being out as long as a and b together.
+pub fn mul(a: &[u64], b: &[u64], out: &mut [u64]) {
+    record(Op::Mul);
+    out.iter_mut().for_each(|o| *o = 0);
+    for (i, b_i) in b.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, a_j) in a.iter().enumerate() {
+            let sum = out[i + j] as u128 + *a_j as u128 * *b_i as u128 + carry as u128;
+            out[i + j] = sum as u64;
+            carry = (sum >> 64) as u64;
+        }
+        out[i + a.len()] = carry;
+    }
+}
+
+/// out = (a + b) mod m for a, b < m.
+pub fn mod_add(a: &[u64], b: &[u64], m: &[u64], out: &mut [u64]) {
+    record(Op::ModAdd);
+    let mut sum = vec![0u64; m.len()];
+    let carry = add(a, b, &mut sum);
+    let borrow = sub(&sum, m, out);
+    // The sum is kept when it didn't overflow and it's below m.
+    cond_assign(bit_mask(borrow & (carry ^ 1)), out, &sum);
+}
+
+/// out = (a - b) mod m for a, b < m.
+pub fn mod_sub(a: &[u64], b: &[u64], m: &[u64], out: &mut [u64]) {
+    record(Op::ModSub);
+    let borrow = sub(a, b, out);
+    let mut wrapped = vec![0u64; m.len()];
+    add(out, m, &mut wrapped);
+    cond_assign(bit_mask(borrow), out, &wrapped);
+}
+
+#[cfg(test)]
+#[test]
+fn constant_time_limbs_work() {
+    assert_eq!(eq_mask(7, 7), u64::MAX);
+    assert_eq!(eq_mask(7, 8), 0);
+    assert_eq!(eq_mask(0, 1 << 63), 0);
+    assert!(eq(&[1, 2, 3], &[1, 2, 3]) && !eq(&[1, 2, 3], &[1, 2, 4]) && !eq(&[0, 2, 3], &[1, 2, 3]));
+
+    let x = BigUint::from_bytes_be(&[0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x11]);
+    assert_eq!(to_limbs(&x, 3), vec![0x3456_789a_bcde_f011, 0x12, 0]);
+    assert_eq!(from_limbs(&to_limbs(&x, 3)), x);
+
+    let table: Vec<Vec<u64>> = (0..8u64).map(|i| vec![i, i * 10]).collect();
+    let mut out = vec![0u64; 2];
+    lookup(&table, 5, &mut out);
+    assert_eq!(out, vec![5, 50]);
+
+    let m = vec![u64::MAX - 1, u64::MAX];
+    let (a, b) = (vec![u64::MAX - 2, u64::MAX], vec![5, 0]);
+    mod_add(&a, &b, &m, &mut out);
+    assert_eq!(out, vec![4, 0]);
+    mod_sub(&b, &a, &m, &mut out);
+    assert_eq!(out, vec![6, 0]);
+    mod_sub(&a, &b, &m, &mut out);
+    assert_eq!(out, vec![u64::MAX - 7, u64::MAX]);
+
+    let mut prod = vec![0u64; 4];
+    mul(&a, &b, &mut prod);
+    assert_eq!(from_limbs(&prod), from_limbs(&a) * from_limbs(&b));
+}
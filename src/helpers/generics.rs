@@ -1,7 +1,10 @@
 //! Generic_Helpers
+use crate::error::Error;
 use crate::types::*;
+use crate::secret::Wipe;
 use num_bigint::{BigUint, BigInt};
-use num::{Zero, One, Signed};
+use num::{Zero, One};
+#[cfg(test)]
 use std::str::FromStr;
 use base64::*;
 use std::str::from_utf8;
@@ -39,68 +42,117 @@ pub fn gen_basic_bigints() -> (BigInt, BigInt, BigInt) {
 }
 
 /// Generate a BigUint from a positive BigInt
-pub fn biguint_from_bigint(a: &BigInt) -> Result<BigUint, &'static str> {
-    if a.is_negative() {return Err("Error converting a negative BigInt to a BigUint")}
-    let boxed = format!("{}", a.clone()).into_boxed_str();
-    let biguint_str = Box::leak(boxed);
-    Ok(BigUint::from_str(biguint_str).unwrap())
+pub fn biguint_from_bigint(a: &BigInt) -> Result<BigUint, Error> {
+    a.to_biguint().ok_or(Error::InvalidParams("Error converting a negative BigInt to a BigUint"))
+}
+
+/// Converts a BigUint into a big-endian octet string of length `len` (I2OSP, RFC 8017 §4.1).
+pub fn i2osp(x: &BigUint, len: usize) -> Result<Vec<u8>, Error> {
+    let bytes = x.to_bytes_be();
+    if bytes.len() > len {return Err(Error::Encoding("Integer too large to be encoded with the requested length"))}
+    let mut res = vec![0u8; len - bytes.len()];
+    res.extend_from_slice(&bytes);
+    Ok(res)
+}
+
+/// Converts a big-endian octet string into a BigUint (OS2IP, RFC 8017 §4.2).
+pub fn os2ip(x: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(x)
+}
+
+#[cfg(test)]
+#[test]
+fn i2osp_os2ip_roundtrip() {
+    let x = BigUint::from(0x0102u32);
+    assert_eq!(i2osp(&x, 4).unwrap(), vec![0, 0, 1, 2]);
+    assert_eq!(os2ip(&[0, 0, 1, 2]), x);
+    assert!(i2osp(&x, 1).is_err());
+    assert_eq!(i2osp(&BigUint::zero(), 2).unwrap(), vec![0, 0]);
+}
+
+/// Encodes bytes as lowercase hex.
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes hex, in upper or lower case, into bytes.
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, Error> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {return Err(Error::Encoding("Invalid hex"))}
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| Error::Encoding("Invalid hex")))
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn hex_roundtrip() {
+    assert_eq!(to_hex(&[0x00, 0x0f, 0xab]), "000fab");
+    assert_eq!(from_hex("000FaB").unwrap(), vec![0x00, 0x0f, 0xab]);
+    assert!(from_hex("0f0").is_err());
+    assert!(from_hex("zz").is_err());
+}
+
+/// Compares two byte slices in constant time (for slices of the same length).
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {return false}
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 // Format Keypair to print it on a file.
-pub fn prepare_to_print(kp: &KeyPair) -> Result<(String, String), &'static str> {
+pub fn prepare_to_print(kp: &KeyPair) -> Result<(String, String), Error> {
     let (mut encoded_pk, mut encoded_sk) = (String::new(), String::new());
     // Encoding Public Key
     encoded_pk.push_str("---------- BEGIN RSA PUBLIC KEY ----------");
-    encoded_pk.push_str("\n");
+    encoded_pk.push('\n');
     encoded_pk.push_str(from_utf8(encode_to_print!(&kp.pk.n)).unwrap());
-    encoded_pk.push_str("\n");
+    encoded_pk.push('\n');
     encoded_pk.push_str(from_utf8(encode_to_print!(&kp.pk.e)).unwrap());
-    encoded_pk.push_str("\n");
+    encoded_pk.push('\n');
     encoded_pk.push_str("----------- END RSA PUBLIC KEY -----------");
-    encoded_pk.push_str("\n");
+    encoded_pk.push('\n');
     encoded_pk.push_str(&kp.size.to_string());
-    encoded_pk.push_str("\n");
+    encoded_pk.push('\n');
     encoded_pk.push_str(&kp.threshold.to_string());
     
     // Encoding Secret Key
     encoded_sk.push_str("---------- BEGIN RSA PRIVATE KEY ----------");
-    encoded_sk.push_str("\n");
+    encoded_sk.push('\n');
     encoded_sk.push_str(from_utf8(encode_to_print!(&kp.sk.n)).unwrap());
-    encoded_sk.push_str("\n");
-    encoded_sk.push_str(from_utf8(encode_to_print!(&kp.sk.d)).unwrap());
-    encoded_sk.push_str("\n");
+    encoded_sk.push('\n');
+    encoded_sk.push_str(from_utf8(encode_to_print!(kp.sk.d.expose_secret())).unwrap());
+    encoded_sk.push('\n');
     encoded_sk.push_str("----------- END RSA PRIVATE KEY -----------");
-    encoded_sk.push_str("\n");
+    encoded_sk.push('\n');
     encoded_sk.push_str(&kp.size.to_string());
-    encoded_sk.push_str("\n");
+    encoded_sk.push('\n');
     encoded_sk.push_str(&kp.threshold.to_string());
     Ok((encoded_pk, encoded_sk))
 }
 
+// Reads the two params, base64 encoded base 16 digits, that follow the header line of a key file.
+fn read_key_params(key_file: &File) -> Result<(BigUint, BigUint), Error> {
+    let mut lines = BufReader::new(key_file).lines().skip(1);
+    let mut next_param = || -> Result<BigUint, Error> {
+        let line = lines.next().ok_or(Error::Encoding("Missing params on the key file"))??;
+        let digits = base64::decode(line.trim()).map_err(|_| Error::Encoding("Invalid base64 on the key file"))?;
+        BigUint::from_radix_be(&digits, 16).ok_or(Error::Encoding("Invalid param on the key file"))
+    };
+    Ok((next_param()?, next_param()?))
+}
+
 /// Gets Public Key params from the Pk file.
 /// Returns a Public Key Struct or an Error.
-pub fn get_pk_params(pk_file: &File) -> Result<PublicKey, &'static str> {
-    let mut lines = vec!();
-    for line in BufReader::new(pk_file).lines() {
-        lines.push(line)
-    }
-    let pk = PublicKey::new(
-    &BigUint::from_radix_be(from_utf8(&base64::decode(&lines.remove(1).unwrap()).unwrap()).unwrap().as_bytes(), 16u32).unwrap(),
-    &BigUint::from_radix_be(from_utf8(&base64::decode(&lines.remove(1).unwrap()).unwrap()).unwrap().as_bytes(), 16u32).unwrap()).unwrap();
-    Ok(pk)
+pub fn get_pk_params(pk_file: &File) -> Result<PublicKey, Error> {
+    let (n, e) = read_key_params(pk_file)?;
+    PublicKey::new(&n, &e)
 }
 
 
 /// Gets ecret Key params from the Pk file.
 /// Returns a ecret Key Struct or an Error.
-pub fn get_sk_params(sk_file: &File) -> Result<SecretKey, &'static str> {
-    let mut lines = vec!();
-    for line in BufReader::new(sk_file).lines() {
-        lines.push(line)
-    }
-    let sk = SecretKey::new(
-    &BigUint::from_radix_be(from_utf8(&base64::decode(&lines.remove(1).unwrap()).unwrap()).unwrap().as_bytes(), 16u32).unwrap(),
-    &BigUint::from_radix_be(from_utf8(&base64::decode(&lines.remove(1).unwrap()).unwrap()).unwrap().as_bytes(), 16u32).unwrap()).unwrap();
-    println!("{}", sk);
-    Ok(sk)
+pub fn get_sk_params(sk_file: &File) -> Result<SecretKey, Error> {
+    let (n, mut d) = read_key_params(sk_file)?;
+    let sk = SecretKey::new(&n, &d);
+    d.wipe();
+    sk
 }
@@ -1,9 +1,11 @@
 //! Math
+#![cfg_attr(test, allow(clippy::bool_assert_comparison, clippy::unnecessary_cast))]
+#[cfg(test)]
 use std::str::FromStr;
-use rand::Rng;
 use num_bigint::{ToBigUint, BigUint, RandBigInt, BigInt, Sign};
-use num::{Zero, One, Integer};
+use num::{Zero, One, Integer, Signed};
 use crate::helpers::generics::*;
+use crate::helpers::montgomery::MontgomeryCtx;
 
 
 const DISCARTERS: [u8; 7] = [3, 5, 7, 11, 13, 17, 19];
@@ -12,8 +14,7 @@ const DISCARTERS: [u8; 7] = [3, 5, 7, 11, 13, 17, 19];
 pub fn gen_big_num(bit_len: &u32) -> BigUint {
     // RNG depends on rng_core crate.
     let mut rng = rand::thread_rng();
-    let a = rng.gen_biguint(bit_len.to_owned() as usize);
-    a
+    rng.gen_biguint(bit_len.to_owned() as usize)
 }
 
 // Given lenght, generates a prime number of that lenght approximately.
@@ -22,15 +23,37 @@ pub fn gen_big_prime(size: &u32, threshold: u32) -> BigUint {
     let mut proposal =  gen_big_num(size);
     // Remove all even numbers to reduce the iterations a half.
     if proposal.is_even() {
-        proposal = proposal + BigUint::one();
+        proposal += BigUint::one();
     }
     while !is_prime(&proposal, threshold) {
         // Steps of 2 to avoid the even numbers on the iterations.
-        proposal =  proposal + 2.to_biguint().unwrap();
+        proposal += 2.to_biguint().unwrap();
     }
     proposal
 }
 
+// Given lenght and a public exponent e, generates a prime p of that lenght approximately
+// regenerating it until gcd(e, p - 1) = 1, so e can be inverted modulo λ(n).
+pub fn gen_rsa_prime(size: &u32, threshold: u32, e: &BigUint) -> BigUint {
+    loop {
+        let p = gen_big_prime(size, threshold);
+        if e.gcd(&(&p - BigUint::one())).is_one() {return p}
+    }
+}
+
+// Carmichael's totient function of n = p * q. λ(n) = lcm(p - 1, q - 1)
+pub fn carmichael_lambda(p: &BigUint, q: &BigUint) -> BigUint {
+    let one = BigUint::one();
+    (p - &one).lcm(&(q - &one))
+}
+
+#[cfg(test)]
+#[test]
+fn carmichael_lambda_works() {
+    assert_eq!(carmichael_lambda(&BigUint::from(61u32), &BigUint::from(53u32)), BigUint::from(780u32));
+    assert_eq!(carmichael_lambda(&BigUint::from(11u32), &BigUint::from(13u32)), BigUint::from(60u32));
+}
+
 // Posible to remove and implement it on gen big prime
 // Given a prime proposal, compute Rabin Miller's algorithm.
 pub fn is_prime(proposal: &BigUint, threshold: u32) -> bool {
@@ -48,16 +71,17 @@ fn rabin_miller(proposal: &BigUint, t: u32) -> bool {
     let (z, o, tw) = gen_basic_biguints();
     let (zero, one, two) = (&z, &o, &tw);
     // If proposal <= 1 Rabin-Miller has to fail.
-    if proposal.clone() <= one.to_owned() {return false};
+    if proposal <= one {return false};
     // If proposal != 2 and modulus 2 = 0, Rabin-Miller fails.
-    if proposal.clone() != two.to_owned() && proposal.clone() % two == zero.to_owned() {return false};
+    if proposal != two && proposal % two == *zero {return false};
     // Discarting proposals divisibles by DISCARTERS improving performance of the algorythm
-    let discarts: Vec<bool> = DISCARTERS.into_iter().map(|x| (proposal % x.to_biguint().unwrap()).is_zero()).collect();
-    println!("{:?}", discarts);
-    for result in discarts {
-        if result == true {return false} 
-    } 
-    
+    if DISCARTERS.iter().any(|x| (proposal % x.to_biguint().unwrap()).is_zero()) {return false}
+    // Every odd proposal left is greater than 1, so the Montgomery context is computed once for all the rounds.
+    let ctx = match MontgomeryCtx::new(proposal) {
+        Some(ctx) => ctx,
+        None => return proposal == two
+    };
+
     // Getting exp to execute mulmod.
     let (s,d) = refactor(proposal);
 
@@ -65,15 +89,15 @@ fn rabin_miller(proposal: &BigUint, t: u32) -> bool {
     while counter < t {
         // Gen rand biguint from a range (2, proposal-2)
         let mut rng = rand::thread_rng();
-        let a = rng.gen_biguint_range(&two , &(proposal - two) );
+        let a = rng.gen_biguint_range(two , &(proposal - two) );
 
-        let mut x = mod_exp_pow(&a, &d, proposal);
-        if x != one.to_owned() && x != proposal.to_owned() - one {
+        let mut x = ctx.mod_exp(&a, &d);
+        if x != *one && x != proposal - one {
             let mut i = zero.clone();
             loop {
-                x = mod_exp_pow(&x, &two, proposal);
-                if x == proposal.to_owned() - one {break;}
-                if x == one.to_owned() || i >= s.clone()- one{return false;};
+                x = ctx.mod_mul(&x, &x);
+                if x == proposal - one {break;}
+                if x == *one || i >= s.clone()- one{return false;};
                 
                 i = i.clone() + one;
             }
@@ -101,8 +125,10 @@ fn rabin_miller_works() {
     assert_eq!(rabin_miller(&19u32.to_biguint().unwrap(), 9), false);
 }
 
-// Modular exponentiation implemented on binary exponentiation (squaring)
+// Modular exponentiation. Odd moduli use Montgomery multiplication with sliding windows,
+// any other one binary exponentiation (squaring).
 pub fn mod_exp_pow(base: &BigUint, exp: &BigUint, md: &BigUint) -> BigUint {
+    if let Some(ctx) = MontgomeryCtx::new(md) {return ctx.mod_exp(base, exp)}
     let mut res = BigUint::one();
     let (zero, one, _) = gen_basic_biguints();
     let (mut base, mut exponent) = (base.clone(), exp.clone());
@@ -127,6 +153,10 @@ fn mod_exp_works() {
 
     let res2 = mod_exp_pow(&BigUint::from(5 as u32), &BigUint::from(3 as u32), &BigUint::from(13 as u32));
     assert_eq!(res2, BigUint::from(8 as u32));
+
+    // Even modulus.
+    let res3 = mod_exp_pow(&BigUint::from(5u32), &BigUint::from(3u32), &BigUint::from(16u32));
+    assert_eq!(res3, BigUint::from(13u32));
 }
 
 // Given a number n, write n − 1 as 2s·d with d odd by factoring powers of 2 from n − 1
@@ -135,8 +165,8 @@ fn refactor(n: &BigUint) -> (BigUint, BigUint) {
   let mut d = n.clone() - one.clone();
 
   while d.is_even() {
-    d = d / two.clone();
-    s = s + one.clone();
+    d /= two.clone();
+    s += one.clone();
   }
   (s, d)
 }
@@ -146,8 +176,8 @@ fn refactor(n: &BigUint) -> (BigUint, BigUint) {
 // ax + by = gcd(a,b)
 pub fn egcd<'a>(a: &'a mut BigInt, b: &'a mut BigInt) -> (BigInt, BigInt, BigInt) {
     // base case
-    if a.to_owned() == BigInt::from(0 as u32) {
-        (b.clone(), BigInt::from(0 as i32), BigInt::from(1 as i32))
+    if *a == BigInt::from(0u32) {
+        (b.clone(), BigInt::from(0i32), BigInt::from(1i32))
     } else {
         let mut b_mod_a = b.clone() % a.clone();
         let ref_b_mod_a = &mut b_mod_a;
@@ -188,6 +218,105 @@ fn egcd_test() {
     assert_eq!(a_r.clone()*x + b_r.clone()*y, g);
 }
 
+// Computes the modular inverse of a modulo m.
+// Returns None if a and m are not co-prime.
+pub fn mod_inverse(a: &BigUint, m: &BigUint) -> Option<BigUint> {
+    let big_m = BigInt::from_biguint(Sign::Plus, m.clone());
+    let (g, x, _) = egcd(&mut BigInt::from_biguint(Sign::Plus, a.clone()), &mut big_m.clone());
+    if !g.is_one() {return None}
+    let mut x = x % &big_m;
+    if x.is_negative() {x = x + &big_m}
+    biguint_from_bigint(&x).ok()
+}
+
+#[cfg(test)]
+#[test]
+fn mod_inverse_works() {
+    let inv = mod_inverse(&BigUint::from(3u32), &BigUint::from(11u32)).unwrap();
+    assert_eq!(inv, BigUint::from(4u32));
+    let inv = mod_inverse(&BigUint::from(17u32), &BigUint::from(3120u32)).unwrap();
+    assert_eq!(inv, BigUint::from(2753u32));
+    assert!(mod_inverse(&BigUint::from(4u32), &BigUint::from(8u32)).is_none());
+}
+
+// Modular exponentiation using the Chinese Remainder Theorem with Garner's recombination (RFC 8017 §5.1.2).
+// m1 = c^dP mod p, m2 = c^dQ mod q, h = qInv * (m1 - m2) mod p, m = m2 + q * h
+pub fn crt_exp_pow(c: &BigUint, p: &BigUint, q: &BigUint, dp: &BigUint, dq: &BigUint, qinv: &BigUint) -> BigUint {
+    let m1 = mod_exp_pow(&(c % p), dp, p);
+    let m2 = mod_exp_pow(&(c % q), dq, q);
+    crt_recombine(&m1, &m2, p, q, qinv)
+}
+
+// Garner's recombination of m1 = c^dP mod p and m2 = c^dQ mod q.
+pub fn crt_recombine(m1: &BigUint, m2: &BigUint, p: &BigUint, q: &BigUint, qinv: &BigUint) -> BigUint {
+    // (m1 - m2) mod p without leaving the unsigned domain.
+    let diff = (m1 + p - (m2 % p)) % p;
+    let h = (qinv * diff) % p;
+    m2 + q * h
+}
+
+#[cfg(test)]
+#[test]
+fn crt_exp_pow_works() {
+    // p = 61, q = 53, n = 3233, e = 17, d = 2753
+    let (p, q) = (BigUint::from(61u32), BigUint::from(53u32));
+    let d = BigUint::from(2753u32);
+    let (dp, dq) = (&d % (&p - 1u32), &d % (&q - 1u32));
+    let qinv = mod_inverse(&q, &p).unwrap();
+    for c in &[0u32, 1, 855, 2790, 3232] {
+        let c = BigUint::from(*c);
+        assert_eq!(crt_exp_pow(&c, &p, &q, &dp, &dq, &qinv), mod_exp_pow(&c, &d, &BigUint::from(3233u32)));
+    }
+}
+
+// Recovers the prime factors p > q of n from e and d (NIST SP 800-56B Rev. 2, Appendix C.2).
+// de - 1 = 2^t * r is a multiple of λ(n), so for a random g some g^(2^j * r) is a non-trivial square root
+// of 1 modulo n with probability at least 1/2 and gcd(y - 1, n) gives a factor.
+// Returns None if they aren't found after 100 tries, which only happens with wrong keys.
+pub fn recover_primes(n: &BigUint, e: &BigUint, d: &BigUint) -> Option<(BigUint, BigUint)> {
+    let one = BigUint::one();
+    let ctx = MontgomeryCtx::new(n)?;
+    let ed = e * d;
+    if ed <= one || ed.is_even() {return None}
+    let k = ed - &one;
+    let mut t = 0usize;
+    let mut r = k;
+    while r.is_even() {
+        r >>= 1;
+        t += 1;
+    }
+    let n_minus_one = n - &one;
+    let mut rng = rand::thread_rng();
+    for _ in 0..100 {
+        // r comes from d, so it's a secret exponent.
+        let mut y = ctx.mod_exp_ct(&rng.gen_biguint_below(n), &r);
+        if y == one || y == n_minus_one {continue}
+        for _ in 0..t {
+            let x = ctx.mod_mul(&y, &y);
+            if x == one {
+                let p = (&y - &one).gcd(n);
+                let q = n / &p;
+                return Some(if p > q {(p, q)} else {(q, p)})
+            }
+            if x == n_minus_one {break}
+            y = x;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+#[test]
+fn recover_primes_works() {
+    // p = 61, q = 53, n = 3233, e = 17, d = 2753
+    let (n, e, d) = (BigUint::from(3233u32), BigUint::from(17u32), BigUint::from(2753u32));
+    assert_eq!(recover_primes(&n, &e, &d), Some((BigUint::from(61u32), BigUint::from(53u32))));
+    // d = e^(-1) mod λ(n) works too.
+    assert_eq!(recover_primes(&n, &e, &BigUint::from(413u32)), Some((BigUint::from(61u32), BigUint::from(53u32))));
+    assert!(recover_primes(&n, &e, &BigUint::from(2754u32)).is_none());
+    assert!(recover_primes(&BigUint::from(3234u32), &e, &d).is_none());
+}
+
 // Given a fi_n, find on the interval (fi_n/2, fi_n) a number 
 // that is co-prime with fi_n
 pub fn find_e(fi_n: &BigUint) -> Result<BigUint, bool> {
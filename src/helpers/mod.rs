@@ -1,2 +1,5 @@
 pub mod math;
-pub mod generics;
\ No newline at end of file
+pub mod generics;
+pub mod montgomery;
+pub mod ct;
+pub mod blinding;
@@ -0,0 +1,454 @@
+//! Montgomery
+//! Modular exponentiation on Montgomery form (P. L. Montgomery, "Modular Multiplication Without Trial Division")
+//! over little-endian vectors of 64-bit limbs. Public exponents use sliding windows (HAC Algorithm 14.85),
+//! secret ones a fixed window with constant-time table lookups so the sequence of operations is always the same.
+use num_bigint::BigUint;
+use num::{Integer, One};
+use std::fmt;
+use std::sync::{Arc, PoisonError, RwLock};
+use zeroize::Zeroize;
+use crate::helpers::math::{mod_exp_pow, crt_exp_pow};
+use crate::helpers::ct::{self, Op, to_limbs, from_limbs};
+use crate::secret::{Wipe, ConstantTimeEq};
+
+// Moduli a key keeps contexts for: n, p and q plus one spare.
+const MAX_CACHED_CONTEXTS: usize = 4;
+// Window size of the constant-time exponentiation. Windows of 4 bits never cross limbs.
+const CT_WINDOW: usize = 4;
+
+/// Precomputed values to multiply modulo an odd n without divisions. The moduli may be the secret
+/// primes of a key, so they're wiped on drop and redacted by `Debug`.
+#[derive(Clone, PartialEq)]
+pub struct MontgomeryCtx {
+    modulus: BigUint,
+    n: Vec<u64>,
+    /// -n^(-1) mod 2^64
+    n_prime: u64,
+    /// R² mod n, being R = 2^(64 * limbs)
+    r2: Vec<u64>,
+    /// R mod n, 1 on Montgomery form.
+    one: Vec<u64>
+}
+
+impl Drop for MontgomeryCtx {
+    fn drop(&mut self) {
+        self.modulus.wipe();
+        for limbs in [&mut self.n, &mut self.r2, &mut self.one] {
+            limbs.wipe();
+        }
+        self.n_prime.zeroize();
+    }
+}
+
+impl fmt::Debug for MontgomeryCtx {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MontgomeryCtx").field("limbs", &self.n.len()).finish_non_exhaustive()
+    }
+}
+
+// Window size for an exponent of the given bits, the same thresholds OpenSSL uses.
+fn window_size(bits: usize) -> usize {
+    match bits {
+        671.. => 6,
+        239..=670 => 5,
+        79..=238 => 4,
+        23..=78 => 3,
+        _ => 1
+    }
+}
+
+impl MontgomeryCtx {
+    /// Creates the context of an odd modulus greater than 1. Returns None for any other modulus.
+    pub fn new(modulus: &BigUint) -> Option<Self> {
+        if modulus.is_even() || modulus.is_one() {return None}
+        let len = modulus.bits().div_ceil(64);
+        let n = to_limbs(modulus, len);
+        // Newton's iteration doubles the correct low bits of n^(-1) mod 2^64 on every step: 1, 2, 4, ..., 64.
+        let mut inv = 1u64;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(n[0].wrapping_mul(inv)));
+        }
+        Some(MontgomeryCtx {
+            modulus: modulus.clone(),
+            r2: to_limbs(&((BigUint::one() << (128 * len)) % modulus), len),
+            one: to_limbs(&((BigUint::one() << (64 * len)) % modulus), len),
+            n,
+            n_prime: inv.wrapping_neg()
+        })
+    }
+
+    /// Returns the modulus of the context.
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    // Computes a * b * R^(-1) mod n into `out` (CIOS method) for a < R and b < n. `t` is scratch space
+    // of limbs + 2. It runs in constant time.
+    fn mont_mul(&self, a: &[u64], b: &[u64], out: &mut [u64], t: &mut [u64]) {
+        ct::record(Op::MontMul);
+        let s = self.n.len();
+        t.iter_mut().for_each(|limb| *limb = 0);
+        for &b_i in b.iter().take(s) {
+            let mut carry = 0u64;
+            for j in 0..s {
+                let sum = t[j] as u128 + a[j] as u128 * b_i as u128 + carry as u128;
+                t[j] = sum as u64;
+                carry = (sum >> 64) as u64;
+            }
+            let sum = t[s] as u128 + carry as u128;
+            t[s] = sum as u64;
+            t[s + 1] = (sum >> 64) as u64;
+
+            // Adding m * n clears the lowest limb, which is then shifted out.
+            let m = t[0].wrapping_mul(self.n_prime);
+            let mut carry = ((t[0] as u128 + m as u128 * self.n[0] as u128) >> 64) as u64;
+            for j in 1..s {
+                let sum = t[j] as u128 + m as u128 * self.n[j] as u128 + carry as u128;
+                t[j - 1] = sum as u64;
+                carry = (sum >> 64) as u64;
+            }
+            let sum = t[s] as u128 + carry as u128;
+            t[s - 1] = sum as u64;
+            t[s] = t[s + 1] + (sum >> 64) as u64;
+        }
+
+        // The result is below 2n, so subtracting n once when it isn't below n leaves it reduced.
+        let borrow = ct::sub(&t[..s], &self.n, out);
+        ct::cond_assign(ct::bit_mask(borrow & (t[s] ^ 1)), out, &t[..s]);
+    }
+
+    // Reduces x modulo n in constant time for x of any number of limbs. Returns the limbs of x mod n.
+    fn reduce_limbs(&self, x: &[u64]) -> Vec<u64> {
+        let s = self.n.len();
+        let (mut res, mut shifted, mut t) = (vec![0u64; s], vec![0u64; s], vec![0u64; s + 2]);
+        let (mut chunk_mont, mut chunk_mod) = (vec![0u64; s], vec![0u64; s]);
+        let mut unit = vec![0u64; s];
+        unit[0] = 1;
+        // x = sum of x_i * R^i, by Horner's rule from the most significant chunk: res * R mod n is
+        // res * R² * R^(-1) and x_i mod n is x_i * R² * R^(-2).
+        for chunk in x.chunks(s).rev() {
+            let mut chunk = chunk.to_vec();
+            chunk.resize(s, 0);
+            self.mont_mul(&res, &self.r2, &mut shifted, &mut t);
+            self.mont_mul(&chunk, &self.r2, &mut chunk_mont, &mut t);
+            self.mont_mul(&chunk_mont, &unit, &mut chunk_mod, &mut t);
+            ct::mod_add(&shifted, &chunk_mod, &self.n, &mut res);
+        }
+        res
+    }
+
+    /// Computes a * b mod n.
+    pub fn mod_mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        let s = self.n.len();
+        let (a, b) = (to_limbs(&(a % &self.modulus), s), to_limbs(&(b % &self.modulus), s));
+        let (mut a_mont, mut res, mut t) = (vec![0u64; s], vec![0u64; s], vec![0u64; s + 2]);
+        // (a * R² * R^(-1)) * b * R^(-1) = a * b
+        self.mont_mul(&a, &self.r2, &mut a_mont, &mut t);
+        self.mont_mul(&a_mont, &b, &mut res, &mut t);
+        from_limbs(&res)
+    }
+
+    /// Computes base^exp mod n with a sliding window over the bits of the exponent.
+    pub fn mod_exp(&self, base: &BigUint, exp: &BigUint) -> BigUint {
+        let s = self.n.len();
+        let bits = exp.bits();
+        if bits == 0 {return BigUint::one()}
+        let exp = to_limbs(exp, bits.div_ceil(64));
+        let bit = |i: usize| (exp[i / 64] >> (i % 64)) & 1 == 1;
+        let mut t = vec![0u64; s + 2];
+
+        // Odd powers g, g^3, ..., g^(2^w - 1) on Montgomery form.
+        let w = window_size(bits);
+        let mut g = vec![0u64; s];
+        self.mont_mul(&to_limbs(&(base % &self.modulus), s), &self.r2, &mut g, &mut t);
+        let mut table = vec![g];
+        if w > 1 {
+            let mut g2 = vec![0u64; s];
+            self.mont_mul(&table[0], &table[0], &mut g2, &mut t);
+            for i in 1..1 << (w - 1) {
+                let mut next = vec![0u64; s];
+                self.mont_mul(&table[i - 1], &g2, &mut next, &mut t);
+                table.push(next);
+            }
+        }
+
+        let (mut acc, mut tmp) = (self.one.clone(), vec![0u64; s]);
+        let mut started = false;
+        let mut i = bits;
+        while i > 0 {
+            if !bit(i - 1) {
+                self.mont_mul(&acc, &acc, &mut tmp, &mut t);
+                std::mem::swap(&mut acc, &mut tmp);
+                i -= 1;
+                continue;
+            }
+            // Longest window of at most w bits ending on a set bit.
+            let mut low = i.saturating_sub(w);
+            while !bit(low) {low += 1}
+            let value = (low..i).rev().fold(0usize, |value, j| (value << 1) | bit(j) as usize);
+            if started {
+                for _ in low..i {
+                    self.mont_mul(&acc, &acc, &mut tmp, &mut t);
+                    std::mem::swap(&mut acc, &mut tmp);
+                }
+                self.mont_mul(&acc, &table[value >> 1], &mut tmp, &mut t);
+                std::mem::swap(&mut acc, &mut tmp);
+            } else {
+                acc.copy_from_slice(&table[value >> 1]);
+                started = true;
+            }
+            i = low;
+        }
+
+        // Leaving Montgomery form: acc * 1 * R^(-1).
+        let mut unit = vec![0u64; s];
+        unit[0] = 1;
+        self.mont_mul(&acc, &unit, &mut tmp, &mut t);
+        from_limbs(&tmp)
+    }
+
+    // Computes base^exp mod n in constant time for a secret exponent, returning the limbs of the result.
+    // Every window of the exponent runs 4 squarings, a lookup that reads the whole table and a multiplication,
+    // also for zero windows, over all the bits of the limbs of the modulus.
+    fn mod_exp_ct_limbs(&self, base: &BigUint, exp: &BigUint) -> Vec<u64> {
+        let s = self.n.len();
+        let bits = (64 * s).max(exp.bits());
+        let windows = bits.div_ceil(CT_WINDOW);
+        let mut exp = to_limbs(exp, (windows * CT_WINDOW).div_ceil(64));
+        let window = |i: usize| (exp[i * CT_WINDOW / 64] >> (i * CT_WINDOW % 64)) & ((1 << CT_WINDOW) - 1);
+        let mut t = vec![0u64; s + 2];
+
+        // Every power g^0, g^1, ..., g^15 on Montgomery form.
+        let mut table = vec![self.one.clone(), vec![0u64; s]];
+        let base = match base.bits() > 128 * s {
+            // Bases that don't fit on twice the limbs of n are public values out of range, like c >= n.
+            true => to_limbs(&(base % &self.modulus), s),
+            false => self.reduce_limbs(&to_limbs(base, 2 * s))
+        };
+        self.mont_mul(&base, &self.r2, &mut table[1], &mut t);
+        for i in 2..1 << CT_WINDOW {
+            let mut next = vec![0u64; s];
+            self.mont_mul(&table[i - 1], &table[1], &mut next, &mut t);
+            table.push(next);
+        }
+
+        let (mut acc, mut tmp, mut entry) = (vec![0u64; s], vec![0u64; s], vec![0u64; s]);
+        ct::lookup(&table, window(windows - 1), &mut acc);
+        for i in (0..windows - 1).rev() {
+            for _ in 0..CT_WINDOW {
+                self.mont_mul(&acc, &acc, &mut tmp, &mut t);
+                std::mem::swap(&mut acc, &mut tmp);
+            }
+            ct::lookup(&table, window(i), &mut entry);
+            self.mont_mul(&acc, &entry, &mut tmp, &mut t);
+            std::mem::swap(&mut acc, &mut tmp);
+        }
+
+        let mut unit = vec![0u64; s];
+        unit[0] = 1;
+        self.mont_mul(&acc, &unit, &mut tmp, &mut t);
+        exp.wipe();
+        tmp
+    }
+
+    /// Computes base^exp mod n in constant time, for exponents that must be kept secret like d.
+    /// Only the lengths of the values, which are public, affect the time it takes.
+    pub fn mod_exp_ct(&self, base: &BigUint, exp: &BigUint) -> BigUint {
+        from_limbs(&self.mod_exp_ct_limbs(base, exp))
+    }
+}
+
+/// Computes c^d mod n with the CRT components of the key in constant time: the exponentiations modulo p and q
+/// and Garner's recombination (RFC 8017 §5.1.2) only use constant-time limb arithmetic.
+pub fn crt_exp_ct(c: &BigUint, ctx_p: &MontgomeryCtx, ctx_q: &MontgomeryCtx, dp: &BigUint, dq: &BigUint, qinv: &BigUint) -> BigUint {
+    let (sp, sq) = (ctx_p.n.len(), ctx_q.n.len());
+    let m1 = ctx_p.mod_exp_ct_limbs(c, dp);
+    let m2 = ctx_q.mod_exp_ct_limbs(c, dq);
+
+    // h = qInv * (m1 - m2) mod p. qInv * diff * R^(-1) * R² * R^(-1) = qInv * diff.
+    let m2_mod_p = ctx_p.reduce_limbs(&m2);
+    let mut t = vec![0u64; sp + 2];
+    let (mut diff, mut tmp, mut h) = (vec![0u64; sp], vec![0u64; sp], vec![0u64; sp]);
+    ct::mod_sub(&m1, &m2_mod_p, &ctx_p.n, &mut diff);
+    let mut qinv = to_limbs(qinv, sp);
+    ctx_p.mont_mul(&diff, &qinv, &mut tmp, &mut t);
+    qinv.wipe();
+    ctx_p.mont_mul(&tmp, &ctx_p.r2, &mut h, &mut t);
+
+    // m = m2 + q * h, below n so it fits on the limbs of p and q.
+    let mut m = vec![0u64; sp + sq];
+    ct::mul(&ctx_q.n, &h, &mut m);
+    let mut m2_wide = m2;
+    m2_wide.resize(sp + sq, 0);
+    let qh = m.clone();
+    ct::add(&qh, &m2_wide, &mut m);
+    from_limbs(&m)
+}
+
+/// Montgomery contexts of the moduli a key works with. They're computed on first use and shared with
+/// the clones of the key, so R² mod n and n' aren't recomputed on every operation.
+#[derive(Clone, Debug, Default)]
+pub struct MontgomeryCache {
+    contexts: Arc<RwLock<Vec<Arc<MontgomeryCtx>>>>
+}
+
+/// Caches hold derived values only, so they never make two keys different.
+impl PartialEq for MontgomeryCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl MontgomeryCache {
+    /// Returns the context of the modulus, computing and caching it when it isn't cached yet.
+    /// Returns None for moduli Montgomery multiplication can't work with.
+    pub fn get(&self, modulus: &BigUint) -> Option<Arc<MontgomeryCtx>> {
+        // The moduli may be the secret primes, so every cached one is compared in constant time.
+        let cached = self.contexts.read().unwrap_or_else(PoisonError::into_inner)
+            .iter().fold(None, |found, ctx| if ctx.modulus().ct_eq(modulus) {Some(Arc::clone(ctx))} else {found});
+        if cached.is_some() {return cached}
+
+        let ctx = Arc::new(MontgomeryCtx::new(modulus)?);
+        let mut contexts = self.contexts.write().unwrap_or_else(PoisonError::into_inner);
+        if contexts.len() >= MAX_CACHED_CONTEXTS {
+            contexts.remove(0);
+        }
+        contexts.push(Arc::clone(&ctx));
+        Some(ctx)
+    }
+
+    /// Computes base^exp mod modulus with the cached context, falling back to `mod_exp_pow` on even moduli.
+    pub fn mod_exp(&self, base: &BigUint, exp: &BigUint, modulus: &BigUint) -> BigUint {
+        match self.get(modulus) {
+            Some(ctx) => ctx.mod_exp(base, exp),
+            None => mod_exp_pow(base, exp, modulus)
+        }
+    }
+
+    /// Computes base^exp mod modulus in constant time with the cached context. Even moduli, which no
+    /// valid key has, fall back to `mod_exp_pow`.
+    pub fn mod_exp_ct(&self, base: &BigUint, exp: &BigUint, modulus: &BigUint) -> BigUint {
+        match self.get(modulus) {
+            Some(ctx) => ctx.mod_exp_ct(base, exp),
+            None => mod_exp_pow(base, exp, modulus)
+        }
+    }
+
+    /// Computes c^d mod n from the CRT components in constant time with the cached contexts of p and q.
+    pub fn crt_exp_ct(&self, c: &BigUint, p: &BigUint, q: &BigUint, dp: &BigUint, dq: &BigUint, qinv: &BigUint) -> BigUint {
+        match (self.get(p), self.get(q)) {
+            (Some(ctx_p), Some(ctx_q)) => crt_exp_ct(c, &ctx_p, &ctx_q, dp, dq, qinv),
+            _ => crt_exp_pow(c, p, q, dp, dq, qinv)
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn montgomery_mod_exp_works() {
+    use num_bigint::RandBigInt;
+    let mut rng = rand::thread_rng();
+    assert!(MontgomeryCtx::new(&BigUint::from(3232u32)).is_none());
+    assert!(MontgomeryCtx::new(&BigUint::one()).is_none());
+
+    let ctx = MontgomeryCtx::new(&BigUint::from(3233u32)).unwrap();
+    assert_eq!(ctx.mod_exp(&BigUint::from(65u32), &BigUint::from(17u32)), BigUint::from(2790u32));
+    assert_eq!(ctx.mod_exp(&BigUint::from(2790u32), &BigUint::from(2753u32)), BigUint::from(65u32));
+    assert_eq!(ctx.mod_exp(&BigUint::from(65u32), &BigUint::from(0u32)), BigUint::one());
+
+    // Moduli of one and several limbs and exponents of every window size.
+    for bits in &[61usize, 64, 127, 521, 1024, 2048] {
+        let modulus = rng.gen_biguint(*bits) | BigUint::one();
+        let ctx = MontgomeryCtx::new(&modulus).unwrap();
+        for exp_bits in &[1usize, 17, 64, 100, 300, 1024] {
+            let base = rng.gen_biguint(bits + 8);
+            let exp = rng.gen_biguint(*exp_bits);
+            assert_eq!(ctx.mod_exp(&base, &exp), base.modpow(&exp, &modulus));
+            assert_eq!(ctx.mod_mul(&base, &exp), (&base * &exp) % &modulus);
+        }
+    }
+
+    let cache = MontgomeryCache::default();
+    let modulus = BigUint::from(3233u32);
+    assert!(Arc::ptr_eq(&cache.get(&modulus).unwrap(), &cache.clone().get(&modulus).unwrap()));
+    assert_eq!(cache.mod_exp(&BigUint::from(5u32), &BigUint::from(3u32), &BigUint::from(16u32)), BigUint::from(13u32));
+}
+
+#[cfg(test)]
+#[test]
+fn constant_time_mod_exp_works() {
+    use num_bigint::RandBigInt;
+    use crate::helpers::math::{gen_big_prime, mod_inverse};
+    let mut rng = rand::thread_rng();
+    for bits in &[61usize, 64, 127, 521, 1024] {
+        let modulus = rng.gen_biguint(*bits) | BigUint::one();
+        let ctx = MontgomeryCtx::new(&modulus).unwrap();
+        for exp in &[BigUint::from(0u32), BigUint::one(), rng.gen_biguint(*bits), rng.gen_biguint(bits + 70)] {
+            for base in &[BigUint::from(0u32), &modulus - 1u32, rng.gen_biguint(*bits), rng.gen_biguint(2 * bits), rng.gen_biguint(4 * bits)] {
+                assert_eq!(ctx.mod_exp_ct(base, exp), base.modpow(exp, &modulus));
+            }
+        }
+    }
+
+    // p = 61, q = 53, n = 3233, e = 17, d = 2753
+    let (p, q, d) = (BigUint::from(61u32), BigUint::from(53u32), BigUint::from(2753u32));
+    let (ctx_p, ctx_q) = (MontgomeryCtx::new(&p).unwrap(), MontgomeryCtx::new(&q).unwrap());
+    let (dp, dq, qinv) = (&d % 60u32, &d % 52u32, mod_inverse(&q, &p).unwrap());
+    for c in 0..3233u32 {
+        let c = BigUint::from(c);
+        assert_eq!(crt_exp_ct(&c, &ctx_p, &ctx_q, &dp, &dq, &qinv), c.modpow(&d, &BigUint::from(3233u32)));
+    }
+
+    // Primes of very different sizes reduce m2 from more than twice the limbs of p.
+    let (p, q) = (gen_big_prime(&64, 9), gen_big_prime(&320, 9));
+    let (ctx_p, ctx_q) = (MontgomeryCtx::new(&p).unwrap(), MontgomeryCtx::new(&q).unwrap());
+    let (dp, dq, qinv) = (rng.gen_biguint(64) % (&p - 1u32), rng.gen_biguint(320) % (&q - 1u32), mod_inverse(&q, &p).unwrap());
+    for c in &[BigUint::from(0u32), BigUint::one(), &p * &q - 1u32, rng.gen_biguint(383)] {
+        let m1 = c.modpow(&dp, &p);
+        let m2 = c.modpow(&dq, &q);
+        let h = (&qinv * ((&m1 + &p) - (&m2 % &p))) % &p;
+        assert_eq!(crt_exp_ct(c, &ctx_p, &ctx_q, &dp, &dq, &qinv), m2 + &q * h);
+    }
+}
+
+// Harness checking that the secret-key operations run the same sequence of operations whatever the data.
+#[cfg(test)]
+#[test]
+fn constant_time_operations_are_data_independent() {
+    use num_bigint::RandBigInt;
+    use crate::helpers::math::{gen_big_prime, mod_inverse};
+    let mut rng = rand::thread_rng();
+    let modulus = rng.gen_biguint(1024) | BigUint::one() | (BigUint::one() << 1023);
+    let ctx = MontgomeryCtx::new(&modulus).unwrap();
+    let all_ones = (BigUint::one() << 1024) - 1u32;
+    let exps = [BigUint::from(0u32), BigUint::one(), BigUint::one() << 1023, &modulus - 1u32, rng.gen_biguint(1024), rng.gen_biguint(512)];
+    let bases = [BigUint::from(0u32), BigUint::one(), &modulus - 1u32, all_ones, rng.gen_biguint(1024), rng.gen_biguint(2048)];
+
+    let (_, reference) = ct::trace(|| ctx.mod_exp_ct(&bases[0], &exps[0]));
+    assert!(reference.iter().filter(|op| **op == Op::Lookup).count() == 1024 / CT_WINDOW);
+    for exp in &exps {
+        for base in &bases {
+            let (res, ops) = ct::trace(|| ctx.mod_exp_ct(base, exp));
+            assert_eq!(res, base.modpow(exp, &modulus));
+            assert!(ops == reference);
+        }
+    }
+    // The sliding window of public exponents does depend on the exponent, which the harness notices.
+    let (_, sparse) = ct::trace(|| ctx.mod_exp(&bases[4], &exps[2]));
+    let (_, dense) = ct::trace(|| ctx.mod_exp(&bases[4], &exps[3]));
+    assert!(sparse != dense);
+
+    // CRT private operations of different keys of the same size and different ciphertexts.
+    let traces: Vec<Vec<Op>> = (0..2).flat_map(|_| {
+        let (p, q) = (gen_big_prime(&256, 9), gen_big_prime(&256, 9));
+        let d = rng.gen_biguint(500);
+        let (ctx_p, ctx_q) = (MontgomeryCtx::new(&p).unwrap(), MontgomeryCtx::new(&q).unwrap());
+        let (dp, dq) = (&d % (&p - 1u32), &d % (&q - 1u32));
+        let qinv = mod_inverse(&q, &p).unwrap_or_else(BigUint::one);
+        let n = &p * &q;
+        vec![BigUint::from(0u32), BigUint::one(), &n - 1u32, rng.gen_biguint_below(&n)].into_iter()
+            .map(|c| ct::trace(|| crt_exp_ct(&c, &ctx_p, &ctx_q, &dp, &dq, &qinv)).1)
+            .collect::<Vec<_>>()
+    }).collect();
+    assert!(traces.iter().all(|ops| *ops == traces[0]));
+}
@@ -0,0 +1,177 @@
+//! Hybrid encryption
+//! Data of any length is encrypted with a random AES-256-GCM key, which is wrapped with RSA-OAEP (SHA-256)
+//! for one or several recipients.
+//!
+//! Envelope layouts, lengths in big-endian:
+//!
+//! - Version 1, single recipient:
+//!   | magic `RSAE` | 1 | wrapped key len (u16) | wrapped key | nonce (12) | ciphertext | tag (16) |
+//! - Version 2, multiple recipients, each slot identified by the recipient key fingerprint:
+//!   | magic `RSAE` | 2 | slot count (u16) | slots | nonce (12) | ciphertext | tag (16) |
+//!   where each slot is | fingerprint (32) | wrapped key len (u16) | wrapped key |
+//!
+//! Everything before the nonce is authenticated as additional data of AES-GCM.
+use rand::Rng;
+use aes_gcm::{Aes256Gcm, KeyInit};
+use aes_gcm::aead::{Aead, Payload};
+use crate::error::Error;
+use crate::types::*;
+use crate::hash::Hash;
+use crate::padding::OaepParams;
+
+pub const MAGIC: &[u8] = b"RSAE";
+pub const VERSION: u8 = 1;
+pub const MULTI_RECIPIENT_VERSION: u8 = 2;
+
+pub(crate) const KEY_LEN: usize = 32;
+pub(crate) const NONCE_LEN: usize = 12;
+pub(crate) const TAG_LEN: usize = 16;
+const FINGERPRINT_LEN: usize = 32;
+/// Most recipients of a multi-recipient envelope, so the slots a key may have to try stay bounded.
+pub const MAX_RECIPIENTS: usize = 256;
+
+// OAEP params used to wrap the content key.
+pub(crate) fn wrap_params() -> OaepParams {
+    OaepParams::from(Hash::Sha256)
+}
+
+// Generates a random AES-256 content key.
+pub(crate) fn gen_content_key() -> Vec<u8> {
+    random_key(KEY_LEN)
+}
+
+fn random_key(len: usize) -> Vec<u8> {
+    let mut key = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut key);
+    key
+}
+
+// Unwraps a content key of key_len bytes. A random key replaces one that can't be unwrapped, so a wrong
+// RSA key and a tampered envelope fail in the same way once the AEAD checks the tag.
+pub(crate) fn unwrap_content_key(wrapped: &[u8], sk: &SecretKey, key_len: usize, params: &OaepParams) -> Vec<u8> {
+    match sk.decrypt_oaep(wrapped, params) {
+        Ok(key) if key.len() == key_len => key,
+        _ => random_key(key_len)
+    }
+}
+
+// Appends the wrapped key preceded by its length.
+fn put_wrapped_key(envelope: &mut Vec<u8>, wrapped: &[u8]) {
+    envelope.extend_from_slice(&(wrapped.len() as u16).to_be_bytes());
+    envelope.extend_from_slice(wrapped);
+}
+
+// Reads the u16 length prefixed field at pos returning it and the position after it.
+fn read_field(envelope: &[u8], pos: usize) -> Result<(&[u8], usize), Error> {
+    if envelope.len() < pos + 2 {return Err(Error::Encoding("Invalid hybrid envelope"))}
+    let len = u16::from_be_bytes([envelope[pos], envelope[pos + 1]]) as usize;
+    if envelope.len() < pos + 2 + len {return Err(Error::Encoding("Invalid hybrid envelope"))}
+    Ok((&envelope[pos + 2..pos + 2 + len], pos + 2 + len))
+}
+
+// Recipient slot: the fingerprint of the recipient key, absent on version 1, and the wrapped key.
+type Slot<'a> = (Option<&'a [u8]>, &'a [u8]);
+
+// Parses the header of an envelope returning its recipient slots and the position of the nonce.
+fn parse_slots(envelope: &[u8]) -> Result<(Vec<Slot<'_>>, usize), Error> {
+    if envelope.len() < MAGIC.len() + 1 || !envelope.starts_with(MAGIC) {return Err(Error::Encoding("Invalid hybrid envelope"))}
+    let pos = MAGIC.len() + 1;
+    match envelope[MAGIC.len()] {
+        VERSION => {
+            let (wrapped, pos) = read_field(envelope, pos)?;
+            Ok((vec![(None, wrapped)], pos))
+        },
+        MULTI_RECIPIENT_VERSION => {
+            if envelope.len() < pos + 2 {return Err(Error::Encoding("Invalid hybrid envelope"))}
+            let count = u16::from_be_bytes([envelope[pos], envelope[pos + 1]]) as usize;
+            if count > MAX_RECIPIENTS {return Err(Error::Unsupported("Too many recipients on hybrid envelope"))}
+            let mut pos = pos + 2;
+            let mut slots = Vec::with_capacity(count);
+            for _ in 0..count {
+                if envelope.len() < pos + FINGERPRINT_LEN {return Err(Error::Encoding("Invalid hybrid envelope"))}
+                let fingerprint = &envelope[pos..pos + FINGERPRINT_LEN];
+                let (wrapped, next) = read_field(envelope, pos + FINGERPRINT_LEN)?;
+                slots.push((Some(fingerprint), wrapped));
+                pos = next;
+            }
+            Ok((slots, pos))
+        },
+        _ => Err(Error::Unsupported("Unsupported hybrid envelope version"))
+    }
+}
+
+// Encrypts the plaintext with the content key appending the nonce, ciphertext and tag to the envelope header.
+fn seal(mut envelope: Vec<u8>, key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let encrypted = Aes256Gcm::new(key.into())
+        .encrypt((&nonce).into(), Payload { msg: plaintext, aad: &envelope })
+        .map_err(|_| Error::MessageTooLong)?;
+    envelope.extend_from_slice(&nonce);
+    envelope.extend_from_slice(&encrypted);
+    Ok(envelope)
+}
+
+/// Encrypts data of any length returning an envelope that the Secret Key of any of the recipients can open.
+pub fn encrypt_multi(recipients: &[PublicKey], plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    if recipients.is_empty() || recipients.len() > MAX_RECIPIENTS {return Err(Error::InvalidParams("Invalid number of recipients"))}
+    let key = gen_content_key();
+    let mut envelope = MAGIC.to_vec();
+    envelope.push(MULTI_RECIPIENT_VERSION);
+    envelope.extend_from_slice(&(recipients.len() as u16).to_be_bytes());
+    for pk in recipients {
+        envelope.extend_from_slice(&pk.fingerprint());
+        put_wrapped_key(&mut envelope, &pk.encrypt_oaep(&key, &wrap_params())?);
+    }
+    seal(envelope, &key, plaintext)
+}
+
+/// Returns the fingerprints of the recipients of an envelope, empty for single recipient envelopes.
+pub fn recipients(envelope: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let (slots, _) = parse_slots(envelope)?;
+    Ok(slots.iter().filter_map(|(fingerprint, _)| fingerprint.map(<[u8]>::to_vec)).collect())
+}
+
+impl PublicKey {
+    /// Encrypts data of any length returning a versioned envelope only the matching Secret Key can open.
+    pub fn encrypt_hybrid(&self, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        let key = gen_content_key();
+        let mut envelope = MAGIC.to_vec();
+        envelope.push(VERSION);
+        put_wrapped_key(&mut envelope, &self.encrypt_oaep(&key, &wrap_params())?);
+        seal(envelope, &key, plaintext)
+    }
+}
+
+impl SecretKey {
+    /// Decrypts an envelope created with `PublicKey::encrypt_hybrid` or `encrypt_multi`. On multi-recipient
+    /// envelopes the slot is found by the fingerprint of the key; a key that doesn't know its public exponent
+    /// unwraps every slot instead and tries the content keys that unwrap. A wrong key or any modification of
+    /// the envelope gives back a decryption error.
+    pub fn decrypt_hybrid(&self, envelope: &[u8]) -> Result<Vec<u8>, Error> {
+        let (slots, nonce_start) = parse_slots(envelope)?;
+        if envelope.len() < nonce_start + NONCE_LEN + TAG_LEN {return Err(Error::Encoding("Invalid hybrid envelope"))}
+        let keys = match (slots.as_slice(), self.e.as_ref()) {
+            ([(None, wrapped)], _) => vec![unwrap_content_key(wrapped, self, KEY_LEN, &wrap_params())],
+            (_, Some(e)) => {
+                let fingerprint = PublicKey::new(&self.n, e)?.fingerprint();
+                let wrapped = slots.iter()
+                    .find(|(slot, _)| *slot == Some(&fingerprint[..]))
+                    .map(|(_, wrapped)| *wrapped)
+                    .ok_or(Error::InvalidKey("The envelope isn't encrypted for this key"))?;
+                vec![unwrap_content_key(wrapped, self, KEY_LEN, &wrap_params())]
+            },
+            // Only the slots that unwrap are tried, so AES-GCM goes over the ciphertext once per candidate key.
+            (_, None) => slots.iter()
+                .filter_map(|(_, wrapped)| self.decrypt_oaep(wrapped, &wrap_params()).ok())
+                .filter(|key| key.len() == KEY_LEN)
+                .collect()
+        };
+
+        let nonce = &envelope[nonce_start..nonce_start + NONCE_LEN];
+        let payload = || Payload { msg: &envelope[nonce_start + NONCE_LEN..], aad: &envelope[..nonce_start] };
+        keys.iter()
+            .find_map(|key| Aes256Gcm::new(key[..].into()).decrypt(nonce.into(), payload()).ok())
+            .ok_or(Error::Decryption)
+    }
+}
@@ -0,0 +1,205 @@
+//! JWE
+//! JSON Web Encryption on the compact serialization (RFC 7516). The random content encryption key
+//! is wrapped with RSA-OAEP (RFC 7518 §4.3) and the payload is encrypted with AES-GCM (RFC 7518 §5.3).
+use rand::Rng;
+use serde_json::{Map, Value};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, KeyInit};
+use aes_gcm::aead::{Aead, Payload};
+use crate::error::Error;
+use crate::types::*;
+use crate::hash::Hash;
+use crate::padding::OaepParams;
+use crate::jws::{b64url, b64url_decode};
+use crate::hybrid::unwrap_content_key;
+
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Key management algorithms of JWE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// RSAES-OAEP using SHA-1 and MGF1 with SHA-1.
+    RsaOaep,
+    /// RSAES-OAEP using SHA-256 and MGF1 with SHA-256.
+    RsaOaep256
+}
+
+impl Algorithm {
+    /// Returns the `alg` header value of the algorithm.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::RsaOaep => "RSA-OAEP",
+            Algorithm::RsaOaep256 => "RSA-OAEP-256"
+        }
+    }
+
+    /// Returns the algorithm of an `alg` header value.
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "RSA-OAEP" => Ok(Algorithm::RsaOaep),
+            "RSA-OAEP-256" => Ok(Algorithm::RsaOaep256),
+            _ => Err(Error::Unsupported("Unsupported JWE key management algorithm"))
+        }
+    }
+
+    // OAEP params used to wrap the content encryption key.
+    fn oaep_params(self) -> OaepParams {
+        match self {
+            Algorithm::RsaOaep => OaepParams::from(Hash::Sha1),
+            Algorithm::RsaOaep256 => OaepParams::from(Hash::Sha256)
+        }
+    }
+}
+
+/// Content encryption algorithms of JWE.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encryption {
+    /// AES-GCM using a 128-bit key.
+    A128Gcm,
+    /// AES-GCM using a 256-bit key.
+    A256Gcm
+}
+
+impl Encryption {
+    /// Returns the `enc` header value of the algorithm.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encryption::A128Gcm => "A128GCM",
+            Encryption::A256Gcm => "A256GCM"
+        }
+    }
+
+    /// Returns the algorithm of an `enc` header value.
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "A128GCM" => Ok(Encryption::A128Gcm),
+            "A256GCM" => Ok(Encryption::A256Gcm),
+            _ => Err(Error::Unsupported("Unsupported JWE content encryption algorithm"))
+        }
+    }
+
+    /// Returns the length of the content encryption key in bytes.
+    pub fn key_len(self) -> usize {
+        match self {
+            Encryption::A128Gcm => 16,
+            Encryption::A256Gcm => 32
+        }
+    }
+
+    // Encrypts the plaintext returning the ciphertext followed by the authentication tag.
+    fn encrypt(self, cek: &[u8], iv: &[u8], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+        let payload = Payload { msg: plaintext, aad };
+        match self {
+            Encryption::A128Gcm => Aes128Gcm::new(cek.into()).encrypt(iv.into(), payload),
+            Encryption::A256Gcm => Aes256Gcm::new(cek.into()).encrypt(iv.into(), payload)
+        }.map_err(|_| Error::MessageTooLong)
+    }
+
+    // Decrypts the ciphertext followed by the authentication tag.
+    fn decrypt(self, cek: &[u8], iv: &[u8], ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+        let payload = Payload { msg: ciphertext, aad };
+        match self {
+            Encryption::A128Gcm => Aes128Gcm::new(cek.into()).decrypt(iv.into(), payload),
+            Encryption::A256Gcm => Aes256Gcm::new(cek.into()).decrypt(iv.into(), payload)
+        }.map_err(|_| Error::Decryption)
+    }
+}
+
+/// JOSE header of a JWE.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub alg: Algorithm,
+    pub enc: Encryption,
+    /// Key ID of the recipient key.
+    pub kid: Option<String>,
+    /// Media type of the token.
+    pub typ: Option<String>,
+    /// Media type of the payload, `JWT` for nested tokens.
+    pub cty: Option<String>
+}
+
+impl Header {
+    /// Creates a header for the algorithms without `kid`, `typ` or `cty`.
+    pub fn new(alg: Algorithm, enc: Encryption) -> Self {
+        Header {
+            alg,
+            enc,
+            kid: None,
+            typ: None,
+            cty: None
+        }
+    }
+
+    // Serializes the header as JSON.
+    fn to_json(&self) -> String {
+        let mut header = Map::new();
+        header.insert("alg".to_string(), Value::String(self.alg.name().to_string()));
+        header.insert("enc".to_string(), Value::String(self.enc.name().to_string()));
+        for (name, value) in &[("kid", &self.kid), ("typ", &self.typ), ("cty", &self.cty)] {
+            if let Some(value) = value {
+                header.insert(name.to_string(), Value::String(value.clone()));
+            }
+        }
+        Value::Object(header).to_string()
+    }
+
+    // Parses the header. Compression and critical extensions aren't supported so they're rejected.
+    fn from_json(json: &[u8]) -> Result<Self, Error> {
+        let header = match serde_json::from_slice(json) {
+            Ok(Value::Object(header)) => header,
+            _ => return Err(Error::Encoding("Invalid JWE header"))
+        };
+        if header.contains_key("crit") {return Err(Error::Unsupported("Unsupported critical JWE header parameters"))}
+        if header.contains_key("zip") {return Err(Error::Unsupported("Compressed JWE payloads aren't supported"))}
+        let required_string = |name: &str| header.get(name).and_then(Value::as_str).ok_or(Error::Encoding("Missing required JWE header parameter"));
+        let optional_string = |name: &str| match header.get(name) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(Error::Encoding("Invalid JWE header"))
+        };
+        Ok(Header {
+            alg: Algorithm::from_name(required_string("alg")?)?,
+            enc: Encryption::from_name(required_string("enc")?)?,
+            kid: optional_string("kid")?,
+            typ: optional_string("typ")?,
+            cty: optional_string("cty")?
+        })
+    }
+}
+
+/// Encrypts the plaintext for the Public Key returning a compact JWE
+/// `header.encrypted_key.iv.ciphertext.tag`. A fresh content encryption key and IV are used on each call.
+pub fn encrypt(header: &Header, plaintext: &[u8], pk: &PublicKey) -> Result<String, Error> {
+    let mut rng = rand::thread_rng();
+    let mut cek = vec![0u8; header.enc.key_len()];
+    rng.fill_bytes(&mut cek);
+    let mut iv = [0u8; IV_LEN];
+    rng.fill_bytes(&mut iv);
+
+    let encrypted_key = pk.encrypt_oaep(&cek, &header.alg.oaep_params())?;
+    // The additional authenticated data is the ASCII of the encoded header.
+    let header_b64 = b64url(header.to_json().as_bytes());
+    let mut ciphertext = header.enc.encrypt(&cek, &iv, plaintext, header_b64.as_bytes())?;
+    let tag = ciphertext.split_off(ciphertext.len() - TAG_LEN);
+    Ok(format!("{}.{}.{}.{}.{}", header_b64, b64url(&encrypted_key), b64url(&iv), b64url(&ciphertext), b64url(&tag)))
+}
+
+/// Decrypts a compact JWE with the Secret Key returning its header and plaintext.
+/// Failures unwrapping the key and authenticating the content give back the same decryption error.
+pub fn decrypt(token: &str, sk: &SecretKey) -> Result<(Header, Vec<u8>), Error> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 5 {return Err(Error::Encoding("A compact JWE must have five parts"))}
+    let header = Header::from_json(&b64url_decode(parts[0])?)?;
+    let encrypted_key = b64url_decode(parts[1])?;
+    let iv = b64url_decode(parts[2])?;
+    let mut ciphertext = b64url_decode(parts[3])?;
+    let tag = b64url_decode(parts[4])?;
+    if iv.len() != IV_LEN || tag.len() != TAG_LEN {return Err(Error::Encoding("Invalid JWE IV or tag length"))}
+
+    // A random key replaces a CEK that can't be unwrapped so both failures look the same,
+    // as RFC 7516 §11.5 recommends against padding oracles.
+    let cek = unwrap_content_key(&encrypted_key, sk, header.enc.key_len(), &header.alg.oaep_params());
+    ciphertext.extend_from_slice(&tag);
+    let plaintext = header.enc.decrypt(&cek, &iv, &ciphertext, parts[0].as_bytes())?;
+    Ok((header, plaintext))
+}
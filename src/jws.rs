@@ -0,0 +1,228 @@
+//! JWS
+//! JSON Web Signatures on the compact serialization (RFC 7515) with the RSA algorithms of RFC 7518 §3.3 and §3.5.
+//! JWTs (RFC 7519) are JWSs whose payload is the JSON claims set.
+use serde_json::{Map, Value};
+use crate::error::Error;
+use crate::types::*;
+use crate::hash::Hash;
+use crate::padding::{PssParams, SaltLength};
+use crate::encoding::jwk::JwkSet;
+
+/// RSA signature algorithms of JWS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// RSASSA-PKCS1-v1_5 using SHA-256.
+    RS256,
+    /// RSASSA-PKCS1-v1_5 using SHA-384.
+    RS384,
+    /// RSASSA-PKCS1-v1_5 using SHA-512.
+    RS512,
+    /// RSASSA-PSS using SHA-256 and MGF1 with SHA-256.
+    PS256,
+    /// RSASSA-PSS using SHA-384 and MGF1 with SHA-384.
+    PS384,
+    /// RSASSA-PSS using SHA-512 and MGF1 with SHA-512.
+    PS512
+}
+
+impl Algorithm {
+    /// Returns the `alg` header value of the algorithm.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::RS256 => "RS256",
+            Algorithm::RS384 => "RS384",
+            Algorithm::RS512 => "RS512",
+            Algorithm::PS256 => "PS256",
+            Algorithm::PS384 => "PS384",
+            Algorithm::PS512 => "PS512"
+        }
+    }
+
+    /// Returns the algorithm of an `alg` header value. Anything but the RSA algorithms is rejected,
+    /// so `none` or HMAC tokens can't be verified with an RSA Public Key.
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "RS256" => Ok(Algorithm::RS256),
+            "RS384" => Ok(Algorithm::RS384),
+            "RS512" => Ok(Algorithm::RS512),
+            "PS256" => Ok(Algorithm::PS256),
+            "PS384" => Ok(Algorithm::PS384),
+            "PS512" => Ok(Algorithm::PS512),
+            _ => Err(Error::Unsupported("Unsupported JWS algorithm for RSA keys"))
+        }
+    }
+
+    /// Returns the hash function used by the algorithm.
+    pub fn hash(self) -> Hash {
+        match self {
+            Algorithm::RS256 | Algorithm::PS256 => Hash::Sha256,
+            Algorithm::RS384 | Algorithm::PS384 => Hash::Sha384,
+            Algorithm::RS512 | Algorithm::PS512 => Hash::Sha512
+        }
+    }
+
+    // PSS params of the PS* algorithms: MGF1 with the same hash and a salt as long as the hash output.
+    fn pss_params(self) -> Option<PssParams> {
+        match self {
+            Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512 => {
+                let hash = self.hash();
+                Some(PssParams::new(hash, hash, SaltLength::Exact(hash.output_len())))
+            },
+            _ => None
+        }
+    }
+}
+
+/// JOSE header of a JWS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Header {
+    pub alg: Algorithm,
+    /// Key ID, used to pick the verification key from a JWK Set.
+    pub kid: Option<String>,
+    /// Media type of the token, `JWT` for JSON Web Tokens.
+    pub typ: Option<String>
+}
+
+impl Header {
+    /// Creates a header for the algorithm without `kid` or `typ`.
+    pub fn new(alg: Algorithm) -> Self {
+        Header {
+            alg,
+            kid: None,
+            typ: None
+        }
+    }
+
+    /// Creates a JWT header (`typ: JWT`) for the algorithm and key ID.
+    pub fn jwt(alg: Algorithm, kid: Option<&str>) -> Self {
+        Header {
+            alg,
+            kid: kid.map(str::to_string),
+            typ: Some("JWT".to_string())
+        }
+    }
+
+    // Serializes the header as JSON.
+    fn to_json(&self) -> String {
+        let mut header = Map::new();
+        header.insert("alg".to_string(), Value::String(self.alg.name().to_string()));
+        if let Some(kid) = &self.kid {
+            header.insert("kid".to_string(), Value::String(kid.clone()));
+        }
+        if let Some(typ) = &self.typ {
+            header.insert("typ".to_string(), Value::String(typ.clone()));
+        }
+        Value::Object(header).to_string()
+    }
+
+    // Parses the header. Critical extensions aren't understood so they're rejected (RFC 7515 §4.1.11).
+    fn from_json(json: &[u8]) -> Result<Self, Error> {
+        let header = match serde_json::from_slice(json) {
+            Ok(Value::Object(header)) => header,
+            _ => return Err(Error::Encoding("Invalid JWS header"))
+        };
+        if header.contains_key("crit") {return Err(Error::Unsupported("Unsupported critical JWS header parameters"))}
+        let alg = header.get("alg").and_then(Value::as_str).ok_or(Error::Encoding("Missing alg on JWS header"))?;
+        let optional_string = |name: &str| match header.get(name) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.clone())),
+            Some(_) => Err(Error::Encoding("Invalid JWS header"))
+        };
+        Ok(Header {
+            alg: Algorithm::from_name(alg)?,
+            kid: optional_string("kid")?,
+            typ: optional_string("typ")?
+        })
+    }
+}
+
+// Unpadded base64url encoding used by every part of the compact serialization.
+pub(crate) fn b64url(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+pub(crate) fn b64url_decode(data: &str) -> Result<Vec<u8>, Error> {
+    base64::decode_config(data, base64::URL_SAFE_NO_PAD).map_err(|_| Error::Encoding("Invalid base64url"))
+}
+
+// Splits a compact JWS in its header, payload and signature parts.
+fn split(token: &str) -> Result<(&str, &str, &str), Error> {
+    let mut parts = token.split('.');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(header), Some(payload), Some(signature), None) => Ok((header, payload, signature)),
+        _ => Err(Error::Encoding("A compact JWS must have three parts"))
+    }
+}
+
+// Signs the ASCII(BASE64URL(header) || '.' || BASE64URL(payload)) signing input.
+fn sign_parts(header: &Header, payload: &[u8], sk: &SecretKey) -> Result<(String, String, String), Error> {
+    let header_b64 = b64url(header.to_json().as_bytes());
+    let payload_b64 = b64url(payload);
+    let signing_input = format!("{}.{}", header_b64, payload_b64);
+    let signature = match header.alg.pss_params() {
+        Some(params) => sk.sign_pss(signing_input.as_bytes(), &params)?,
+        None => sk.sign_pkcs1v15(signing_input.as_bytes(), header.alg.hash())?
+    };
+    Ok((header_b64, payload_b64, b64url(&signature)))
+}
+
+// Parses the header checking its algorithm is one of the allowed ones. The header is chosen by whoever
+// made the token, so the verifier must pin the algorithms it accepts.
+fn allowed_header(header_b64: &str, allowed: &[Algorithm]) -> Result<Header, Error> {
+    let header = Header::from_json(&b64url_decode(header_b64)?)?;
+    if !allowed.contains(&header.alg) {return Err(Error::Unsupported("JWS algorithm isn't allowed"))}
+    Ok(header)
+}
+
+// Verifies the signature over the header and payload parts with the algorithm of the header.
+fn verify_parts(header_b64: &str, payload_b64: &str, signature_b64: &str, pk: &PublicKey, allowed: &[Algorithm]) -> Result<Header, Error> {
+    let header = allowed_header(header_b64, allowed)?;
+    let signature = b64url_decode(signature_b64)?;
+    let signing_input = format!("{}.{}", header_b64, payload_b64);
+    match header.alg.pss_params() {
+        Some(params) => pk.verify_pss(signing_input.as_bytes(), &signature, &params)?,
+        None => pk.verify_pkcs1v15(signing_input.as_bytes(), &signature, header.alg.hash())?
+    }
+    Ok(header)
+}
+
+/// Signs the payload returning a compact JWS `header.payload.signature`.
+pub fn sign(header: &Header, payload: &[u8], sk: &SecretKey) -> Result<String, Error> {
+    let (header_b64, payload_b64, signature_b64) = sign_parts(header, payload, sk)?;
+    Ok(format!("{}.{}.{}", header_b64, payload_b64, signature_b64))
+}
+
+/// Signs the payload returning a compact JWS with detached content `header..signature` (RFC 7515 Appendix F).
+pub fn sign_detached(header: &Header, payload: &[u8], sk: &SecretKey) -> Result<String, Error> {
+    let (header_b64, _, signature_b64) = sign_parts(header, payload, sk)?;
+    Ok(format!("{}..{}", header_b64, signature_b64))
+}
+
+/// Verifies a compact JWS returning its header and payload.
+/// The `alg` header must be one of the `allowed` algorithms, which only holds RSA ones.
+pub fn verify(token: &str, pk: &PublicKey, allowed: &[Algorithm]) -> Result<(Header, Vec<u8>), Error> {
+    let (header_b64, payload_b64, signature_b64) = split(token)?;
+    let header = verify_parts(header_b64, payload_b64, signature_b64, pk, allowed)?;
+    Ok((header, b64url_decode(payload_b64)?))
+}
+
+/// Verifies a compact JWS with detached content against the given payload returning its header.
+/// The `alg` header must be one of the `allowed` algorithms.
+pub fn verify_detached(token: &str, payload: &[u8], pk: &PublicKey, allowed: &[Algorithm]) -> Result<Header, Error> {
+    let (header_b64, payload_b64, signature_b64) = split(token)?;
+    if !payload_b64.is_empty() {return Err(Error::Encoding("The JWS payload isn't detached"))}
+    verify_parts(header_b64, &b64url(payload), signature_b64, pk, allowed)
+}
+
+/// Verifies a compact JWS with the key of the JWK Set matching its `kid` header.
+/// The `alg` header must be one of the `allowed` algorithms and match the `alg` of the JWK when it has one.
+pub fn verify_with_jwks(token: &str, jwks: &JwkSet, allowed: &[Algorithm]) -> Result<(Header, Vec<u8>), Error> {
+    let (header_b64, _, _) = split(token)?;
+    let header = allowed_header(header_b64, allowed)?;
+    let kid = header.kid.as_ref().ok_or(Error::Encoding("Missing kid on JWS header"))?;
+    let jwk = jwks.find(kid).ok_or(Error::InvalidKey("No key on the JWK Set matches the JWS kid"))?;
+    if jwk.alg.as_ref().is_some_and(|alg| alg != header.alg.name()) {
+        return Err(Error::InvalidKey("The JWS algorithm doesn't match the alg of the JWK"))
+    }
+    verify(token, &jwk.public_key()?, allowed)
+}
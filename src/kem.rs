@@ -0,0 +1,110 @@
+//! RSA-KEM
+//! Key encapsulation of ISO 18033-2 and RFC 5990: a random z < n is encrypted with the raw RSA
+//! function and the shared secret is derived from I2OSP(z, k) with a key derivation function.
+use num_bigint::RandBigInt;
+use hkdf::Hkdf;
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use crate::error::Error;
+use crate::types::*;
+use crate::hash::Hash;
+use crate::helpers::generics::{i2osp, os2ip};
+
+/// Key derivation function used to turn z into the shared secret.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KemKdf {
+    /// KDF3 of ANS X9.44 as used by RFC 5990: Hash(I2OSP(counter, 4) || Z || info) with the counter
+    /// starting at 1, the same as the one-step KDF of NIST SP 800-56C.
+    Kdf3(Hash),
+    /// HKDF (RFC 5869) with an empty salt, Z as input keying material and info as context.
+    Hkdf(Hash)
+}
+
+/// Parameters of RSA-KEM.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KemParams {
+    pub kdf: KemKdf,
+    /// Length of the shared secret in bytes.
+    pub key_len: usize,
+    /// Context bound to the shared secret (otherInfo of KDF3, info of HKDF).
+    pub info: Vec<u8>
+}
+
+impl Default for KemParams {
+    /// KDF3 with SHA-256, a 32 bytes shared secret and no context.
+    fn default() -> Self {
+        KemParams {
+            kdf: KemKdf::Kdf3(Hash::Sha256),
+            key_len: 32,
+            info: vec!()
+        }
+    }
+}
+
+impl KemParams {
+    /// Creates KEM params with the selected key derivation function, shared secret length and context.
+    pub fn new(kdf: KemKdf, key_len: usize, info: &[u8]) -> Self {
+        KemParams {
+            kdf,
+            key_len,
+            info: info.to_vec()
+        }
+    }
+
+    // Derives the shared secret from the encoded z.
+    fn derive(&self, z: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.key_len == 0 {return Err(Error::InvalidParams("The shared secret length must be positive"))}
+        match self.kdf {
+            KemKdf::Kdf3(hash) => {
+                let mut res = Vec::with_capacity(self.key_len + hash.output_len());
+                let mut counter = 1u32;
+                while res.len() < self.key_len {
+                    res.extend_from_slice(&hash.digest(&[&counter.to_be_bytes()[..], z, &self.info].concat()));
+                    counter = counter.checked_add(1).ok_or(Error::InvalidParams("Shared secret too long"))?;
+                }
+                res.truncate(self.key_len);
+                Ok(res)
+            },
+            KemKdf::Hkdf(hash) => {
+                let mut res = vec![0u8; self.key_len];
+                match hash {
+                    Hash::Sha1 => Hkdf::<Sha1>::new(None, z).expand(&self.info, &mut res),
+                    Hash::Sha256 => Hkdf::<Sha256>::new(None, z).expand(&self.info, &mut res),
+                    Hash::Sha384 => Hkdf::<Sha384>::new(None, z).expand(&self.info, &mut res),
+                    Hash::Sha512 => Hkdf::<Sha512>::new(None, z).expand(&self.info, &mut res)
+                }.map_err(|_| Error::InvalidParams("Shared secret too long"))?;
+                Ok(res)
+            }
+        }
+    }
+}
+
+/// Generates a shared secret for the Public Key with the default params.
+/// Returns the ciphertext to send to the owner of the Secret Key and the shared secret.
+pub fn encapsulate(pk: &PublicKey) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    encapsulate_with_params(pk, &KemParams::default())
+}
+
+/// Recovers the shared secret of a ciphertext with the default params.
+pub fn decapsulate(sk: &SecretKey, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+    decapsulate_with_params(sk, ciphertext, &KemParams::default())
+}
+
+/// Generates a shared secret for the Public Key returning the ciphertext and the shared secret.
+pub fn encapsulate_with_params(pk: &PublicKey, params: &KemParams) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let k = pk.size_in_bytes();
+    let z = rand::thread_rng().gen_biguint_below(&pk.n);
+    let ciphertext = i2osp(&pk.raw_public_op(&z), k)?;
+    let secret = params.derive(&i2osp(&z, k)?)?;
+    Ok((ciphertext, secret))
+}
+
+/// Recovers the shared secret of a ciphertext created with `encapsulate_with_params` and the same params.
+pub fn decapsulate_with_params(sk: &SecretKey, ciphertext: &[u8], params: &KemParams) -> Result<Vec<u8>, Error> {
+    let k = sk.size_in_bytes();
+    if ciphertext.len() != k {return Err(Error::InvalidPadding)}
+    let c = os2ip(ciphertext);
+    if c >= sk.n {return Err(Error::InvalidPadding)}
+    let z = sk.raw_private_op(&c);
+    params.derive(&i2osp(&z, k)?)
+}
@@ -2,6 +2,18 @@ extern crate num;
 extern crate rand;
 extern crate num_bigint;
 
+pub mod error;
+pub mod secret;
 pub mod helpers;
 pub mod types;
+pub mod hash;
+pub mod padding;
+pub mod encoding;
+pub mod jws;
+pub mod jwe;
+pub mod hybrid;
+pub mod stream;
+pub mod kem;
+
+pub use error::Error;
 
@@ -0,0 +1,406 @@
+//! Padding
+use rand::Rng;
+use crate::error::Error;
+use crate::hash::Hash;
+use crate::helpers::generics::constant_time_eq;
+
+/// Minimum amount of padding bytes required by RSAES-PKCS1-v1_5 (0x00 || 0x02 || PS(8) || 0x00).
+pub const PKCS1V15_OVERHEAD: usize = 11;
+
+/// Pads a message following EME-PKCS1-v1_5 (RFC 8017 §7.2.1) for a modulus of `k` bytes.
+/// Returns EM = 0x00 || 0x02 || PS || 0x00 || M where PS are random non-zero bytes.
+pub fn pkcs1v15_pad(msg: &[u8], k: usize) -> Result<Vec<u8>, Error> {
+    if k < PKCS1V15_OVERHEAD || msg.len() > k - PKCS1V15_OVERHEAD {
+        return Err(Error::MessageTooLong)
+    }
+    let mut rng = rand::thread_rng();
+    let ps_len = k - msg.len() - 3;
+    let mut em = Vec::with_capacity(k);
+    em.push(0x00);
+    em.push(0x02);
+    while em.len() < ps_len + 2 {
+        // Padding bytes must never be zero since 0x00 marks the end of PS.
+        let byte: u8 = rng.gen();
+        if byte != 0 {em.push(byte)}
+    }
+    em.push(0x00);
+    em.extend_from_slice(msg);
+    Ok(em)
+}
+
+/// Removes EME-PKCS1-v1_5 padding (RFC 8017 §7.2.2) from an encoded message of `k` bytes.
+/// The whole buffer is always scanned and a single error is returned for every malformed
+/// encoding so the result doesn't act as a padding oracle.
+pub fn pkcs1v15_unpad(em: &[u8], k: usize) -> Result<Vec<u8>, Error> {
+    if k < PKCS1V15_OVERHEAD || em.len() != k {
+        return Err(Error::InvalidPadding)
+    }
+    let mut invalid = (em[0] != 0x00) as u8 | (em[1] != 0x02) as u8;
+    // Index of the first zero byte after PS. Zero means it has not been found yet.
+    let mut separator = 0usize;
+    for (i, byte) in em.iter().enumerate().skip(2) {
+        let is_first_zero = (*byte == 0) & (separator == 0);
+        separator |= i * is_first_zero as usize;
+    }
+    // PS must be at least 8 bytes long.
+    invalid |= (separator < 10) as u8;
+    if invalid != 0 {
+        return Err(Error::InvalidPadding)
+    }
+    Ok(em[separator + 1..].to_vec())
+}
+
+#[cfg(test)]
+#[test]
+fn pkcs1v15_pad_unpad() {
+    let msg = b"Lorem ipsum dolor sit amet";
+    let em = pkcs1v15_pad(msg, 128).unwrap();
+    assert_eq!(em.len(), 128);
+    assert_eq!(&em[..2], &[0x00, 0x02]);
+    assert!(em[2..128 - msg.len() - 1].iter().all(|b| *b != 0));
+    assert_eq!(pkcs1v15_unpad(&em, 128).unwrap(), msg.to_vec());
+
+    // Empty and maximum-length messages.
+    assert_eq!(pkcs1v15_unpad(&pkcs1v15_pad(b"", 64).unwrap(), 64).unwrap(), Vec::<u8>::new());
+    assert!(pkcs1v15_pad(&[0x41; 53], 64).is_ok());
+    assert!(pkcs1v15_pad(&[0x41; 54], 64).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn pkcs1v15_unpad_is_strict() {
+    let em = pkcs1v15_pad(b"msg", 64).unwrap();
+    // Wrong leading bytes.
+    let mut bad = em.clone();
+    bad[0] = 0x01;
+    assert!(pkcs1v15_unpad(&bad, 64).is_err());
+    let mut bad = em.clone();
+    bad[1] = 0x01;
+    assert!(pkcs1v15_unpad(&bad, 64).is_err());
+    // PS shorter than 8 bytes.
+    let mut bad = em.clone();
+    bad[9] = 0x00;
+    assert!(pkcs1v15_unpad(&bad, 64).is_err());
+    // No separator at all.
+    let mut bad = vec![0x00, 0x02];
+    bad.extend_from_slice(&[0xff; 62]);
+    assert!(pkcs1v15_unpad(&bad, 64).is_err());
+    // Wrong length.
+    assert!(pkcs1v15_unpad(&em[1..], 64).is_err());
+}
+
+/// Mask Generation Function MGF1 (RFC 8017 §B.2.1).
+/// Generates a mask of `len` bytes from the seed using the given hash function.
+pub fn mgf1(seed: &[u8], len: usize, hash: Hash) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(len + hash.output_len());
+    let mut counter = 0u32;
+    while mask.len() < len {
+        let mut block = seed.to_vec();
+        block.extend_from_slice(&counter.to_be_bytes());
+        mask.extend_from_slice(&hash.digest(&block));
+        counter += 1;
+    }
+    mask.truncate(len);
+    mask
+}
+
+/// Parameters of the RSAES-OAEP encryption scheme.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OaepParams {
+    pub hash: Hash,
+    pub mgf_hash: Hash,
+    pub label: Vec<u8>
+}
+
+impl Default for OaepParams {
+    /// Creates the default OAEP params defined by RFC 8017: SHA-1 for both hash and MGF1 and an empty label.
+    fn default() -> Self {
+        OaepParams {
+            hash: Hash::Sha1,
+            mgf_hash: Hash::Sha1,
+            label: Vec::new()
+        }
+    }
+}
+
+impl From<Hash> for OaepParams {
+    /// Creates OAEP params that use the same hash for the label and MGF1 with an empty label.
+    fn from(hash: Hash) -> Self {
+        OaepParams {
+            hash,
+            mgf_hash: hash,
+            label: Vec::new()
+        }
+    }
+}
+
+impl OaepParams {
+    /// Creates OAEP params with the selected hash, MGF1 hash and label.
+    pub fn new(hash: Hash, mgf_hash: Hash, label: &[u8]) -> Self {
+        OaepParams {
+            hash,
+            mgf_hash,
+            label: label.to_vec()
+        }
+    }
+}
+
+/// Pads a message following EME-OAEP (RFC 8017 §7.1.1) for a modulus of `k` bytes.
+/// Returns EM = 0x00 || maskedSeed || maskedDB.
+pub fn oaep_pad(msg: &[u8], k: usize, params: &OaepParams) -> Result<Vec<u8>, Error> {
+    let h_len = params.hash.output_len();
+    if k < 2 * h_len + 2 || msg.len() > k - 2 * h_len - 2 {
+        return Err(Error::MessageTooLong)
+    }
+    // DB = lHash || PS || 0x01 || M
+    let mut db = params.hash.digest(&params.label);
+    db.resize(k - msg.len() - h_len - 2, 0x00);
+    db.push(0x01);
+    db.extend_from_slice(msg);
+
+    let mut rng = rand::thread_rng();
+    let mut seed = vec![0u8; h_len];
+    rng.fill_bytes(&mut seed);
+
+    let db_mask = mgf1(&seed, k - h_len - 1, params.mgf_hash);
+    db.iter_mut().zip(db_mask.iter()).for_each(|(b, m)| *b ^= m);
+    let seed_mask = mgf1(&db, h_len, params.mgf_hash);
+    seed.iter_mut().zip(seed_mask.iter()).for_each(|(b, m)| *b ^= m);
+
+    let mut em = Vec::with_capacity(k);
+    em.push(0x00);
+    em.extend_from_slice(&seed);
+    em.extend_from_slice(&db);
+    Ok(em)
+}
+
+/// Removes EME-OAEP padding (RFC 8017 §7.1.2) from an encoded message of `k` bytes.
+/// As on PKCS#1 v1.5, every malformed encoding gives back the same error.
+pub fn oaep_unpad(em: &[u8], k: usize, params: &OaepParams) -> Result<Vec<u8>, Error> {
+    let h_len = params.hash.output_len();
+    if k < 2 * h_len + 2 || em.len() != k {
+        return Err(Error::InvalidPadding)
+    }
+    let l_hash = params.hash.digest(&params.label);
+    let (masked_seed, masked_db) = em[1..].split_at(h_len);
+
+    let seed_mask = mgf1(masked_db, h_len, params.mgf_hash);
+    let seed: Vec<u8> = masked_seed.iter().zip(seed_mask.iter()).map(|(b, m)| b ^ m).collect();
+    let db_mask = mgf1(&seed, k - h_len - 1, params.mgf_hash);
+    let db: Vec<u8> = masked_db.iter().zip(db_mask.iter()).map(|(b, m)| b ^ m).collect();
+
+    let mut invalid = (em[0] != 0x00) as u8;
+    for (a, b) in db[..h_len].iter().zip(l_hash.iter()) {
+        invalid |= a ^ b;
+    }
+    // Look for the 0x01 separator. Every byte before it must be zero.
+    let mut separator = 0usize;
+    for (i, byte) in db.iter().enumerate().skip(h_len) {
+        let not_found = separator == 0;
+        separator |= i * ((*byte == 0x01) & not_found) as usize;
+        invalid |= ((*byte != 0x00) & (*byte != 0x01) & not_found) as u8;
+    }
+    invalid |= (separator == 0) as u8;
+    if invalid != 0 {
+        return Err(Error::InvalidPadding)
+    }
+    Ok(db[separator + 1..].to_vec())
+}
+
+#[cfg(test)]
+#[test]
+fn mgf1_works() {
+    // Expected masks are the leading bytes of Hash(seed || 0x00000000).
+    let mask = mgf1(b"foo", 3, Hash::Sha1);
+    assert_eq!(mask, vec![0x1a, 0xc9, 0x07]);
+    let mask = mgf1(b"bar", 50, Hash::Sha256);
+    assert_eq!(&mask[..4], &[0x38, 0x25, 0x76, 0xa7]);
+    assert_eq!(mask.len(), 50);
+}
+
+#[cfg(test)]
+#[test]
+fn oaep_pad_unpad() {
+    for hash in &[Hash::Sha1, Hash::Sha256, Hash::Sha384, Hash::Sha512] {
+        let params = OaepParams::new(*hash, Hash::Sha1, b"label");
+        let em = oaep_pad(b"Lorem ipsum", 256, &params).unwrap();
+        assert_eq!(em.len(), 256);
+        assert_eq!(oaep_unpad(&em, 256, &params).unwrap(), b"Lorem ipsum".to_vec());
+        // A different label must be rejected.
+        assert!(oaep_unpad(&em, 256, &OaepParams::new(*hash, Hash::Sha1, b"other")).is_err());
+    }
+    let params = OaepParams::from(Hash::Sha256);
+    assert!(oaep_pad(&[0x41; 190], 256, &params).is_ok());
+    assert!(oaep_pad(&[0x41; 191], 256, &params).is_err());
+    assert_eq!(oaep_unpad(&oaep_pad(b"", 256, &params).unwrap(), 256, &params).unwrap(), Vec::<u8>::new());
+}
+
+/// Encodes a message digest following EMSA-PKCS1-v1_5 (RFC 8017 §9.2) for a modulus of `k` bytes.
+/// Returns EM = 0x00 || 0x01 || PS || 0x00 || DigestInfo where PS are 0xff bytes.
+pub fn emsa_pkcs1v15_encode(hashed: &[u8], k: usize, hash: Hash) -> Result<Vec<u8>, Error> {
+    if hashed.len() != hash.output_len() {return Err(Error::InvalidParams("Digest length doesn't match the hash function"))}
+    let prefix = hash.digest_info_prefix();
+    let t_len = prefix.len() + hashed.len();
+    if k < t_len + PKCS1V15_OVERHEAD {
+        return Err(Error::InvalidKey("Intended encoded message length too short"))
+    }
+    let mut em = vec![0xff; k];
+    em[0] = 0x00;
+    em[1] = 0x01;
+    em[k - t_len - 1] = 0x00;
+    em[k - t_len..k - hashed.len()].copy_from_slice(prefix);
+    em[k - hashed.len()..].copy_from_slice(hashed);
+    Ok(em)
+}
+
+#[cfg(test)]
+#[test]
+fn emsa_pkcs1v15_encoding() {
+    let hashed = Hash::Sha256.digest(b"abc");
+    let em = emsa_pkcs1v15_encode(&hashed, 128, Hash::Sha256).unwrap();
+    assert_eq!(em.len(), 128);
+    assert_eq!(&em[..2], &[0x00, 0x01]);
+    assert!(em[2..128 - 51 - 1].iter().all(|b| *b == 0xff));
+    assert_eq!(em[128 - 52], 0x00);
+    assert_eq!(&em[128 - 51..128 - 32], Hash::Sha256.digest_info_prefix());
+    assert_eq!(&em[128 - 32..], &hashed[..]);
+
+    assert!(emsa_pkcs1v15_encode(&hashed, 61, Hash::Sha256).is_err());
+    assert!(emsa_pkcs1v15_encode(&hashed, 128, Hash::Sha512).is_err());
+}
+
+/// Salt length used by the RSASSA-PSS signature scheme.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaltLength {
+    /// Salt of exactly the given amount of bytes.
+    Exact(usize),
+    /// When signing, uses a salt as long as the digest. When verifying, the salt length is
+    /// recovered from the encoded message so any length is accepted.
+    Auto
+}
+
+/// Parameters of the RSASSA-PSS signature scheme.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PssParams {
+    pub hash: Hash,
+    pub mgf_hash: Hash,
+    pub salt_len: SaltLength
+}
+
+impl Default for PssParams {
+    /// Creates PSS params with SHA-256 for both hash and MGF1 and an automatic salt length.
+    fn default() -> Self {
+        PssParams::from(Hash::Sha256)
+    }
+}
+
+impl From<Hash> for PssParams {
+    /// Creates PSS params that use the same hash for the message and MGF1 and an automatic salt length.
+    fn from(hash: Hash) -> Self {
+        PssParams {
+            hash,
+            mgf_hash: hash,
+            salt_len: SaltLength::Auto
+        }
+    }
+}
+
+impl PssParams {
+    /// Creates PSS params with the selected hash, MGF1 hash and salt length.
+    pub fn new(hash: Hash, mgf_hash: Hash, salt_len: SaltLength) -> Self {
+        PssParams {
+            hash,
+            mgf_hash,
+            salt_len
+        }
+    }
+}
+
+// Computes H = Hash(0x00 * 8 || mHash || salt) as defined on RFC 8017 §9.1.1 step 5 and 6.
+fn pss_hash(m_hash: &[u8], salt: &[u8], hash: Hash) -> Vec<u8> {
+    let mut m_prime = vec![0u8; 8];
+    m_prime.extend_from_slice(m_hash);
+    m_prime.extend_from_slice(salt);
+    hash.digest(&m_prime)
+}
+
+/// Encodes a message digest following EMSA-PSS (RFC 8017 §9.1.1) for an encoded message of `em_bits` bits.
+/// Returns EM = maskedDB || H || 0xbc.
+pub fn emsa_pss_encode(m_hash: &[u8], em_bits: usize, params: &PssParams) -> Result<Vec<u8>, Error> {
+    let h_len = params.hash.output_len();
+    if m_hash.len() != h_len {return Err(Error::InvalidParams("Digest length doesn't match the hash function"))}
+    let s_len = match params.salt_len {
+        SaltLength::Exact(len) => len,
+        SaltLength::Auto => h_len
+    };
+    let em_len = em_bits.div_ceil(8);
+    if em_len < h_len + s_len + 2 {
+        return Err(Error::InvalidKey("Encoding error. The modulus is too small for the selected hash and salt length"))
+    }
+    let mut rng = rand::thread_rng();
+    let mut salt = vec![0u8; s_len];
+    rng.fill_bytes(&mut salt);
+    let h = pss_hash(m_hash, &salt, params.hash);
+
+    // DB = PS || 0x01 || salt
+    let mut db = vec![0u8; em_len - s_len - h_len - 2];
+    db.push(0x01);
+    db.extend_from_slice(&salt);
+    let db_mask = mgf1(&h, em_len - h_len - 1, params.mgf_hash);
+    db.iter_mut().zip(db_mask.iter()).for_each(|(b, m)| *b ^= m);
+    // Clear the leftmost 8 * emLen - emBits bits.
+    db[0] &= 0xff >> (8 * em_len - em_bits);
+
+    let mut em = db;
+    em.extend_from_slice(&h);
+    em.push(0xbc);
+    Ok(em)
+}
+
+/// Verifies an EMSA-PSS encoded message (RFC 8017 §9.1.2) of `em_bits` bits against the message digest.
+pub fn emsa_pss_verify(m_hash: &[u8], em: &[u8], em_bits: usize, params: &PssParams) -> Result<(), Error> {
+    let h_len = params.hash.output_len();
+    let em_len = em_bits.div_ceil(8);
+    if m_hash.len() != h_len || em.len() != em_len || em_len < h_len + 2 {return Err(Error::Verification)}
+    if em[em_len - 1] != 0xbc {return Err(Error::Verification)}
+    let (masked_db, h) = em[..em_len - 1].split_at(em_len - h_len - 1);
+    let top_mask = 0xff >> (8 * em_len - em_bits);
+    if masked_db[0] & !top_mask != 0 {return Err(Error::Verification)}
+
+    let db_mask = mgf1(h, em_len - h_len - 1, params.mgf_hash);
+    let mut db: Vec<u8> = masked_db.iter().zip(db_mask.iter()).map(|(b, m)| b ^ m).collect();
+    db[0] &= top_mask;
+
+    // DB = PS || 0x01 || salt where PS are zero bytes.
+    let separator = match db.iter().position(|b| *b != 0x00) {
+        Some(pos) if db[pos] == 0x01 => pos,
+        _ => return Err(Error::Verification)
+    };
+    let salt = &db[separator + 1..];
+    if let SaltLength::Exact(len) = params.salt_len {
+        if salt.len() != len {return Err(Error::Verification)}
+    }
+    if !constant_time_eq(h, &pss_hash(m_hash, salt, params.hash)) {return Err(Error::Verification)}
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn emsa_pss_encode_verify() {
+    let m_hash = Hash::Sha256.digest(b"abc");
+    for salt_len in &[SaltLength::Auto, SaltLength::Exact(0), SaltLength::Exact(20)] {
+        let params = PssParams::new(Hash::Sha256, Hash::Sha1, *salt_len);
+        // Encoded messages whose length in bits is and isn't a multiple of 8.
+        for em_bits in &[1023usize, 1020] {
+            let em = emsa_pss_encode(&m_hash, *em_bits, &params).unwrap();
+            assert_eq!(em.len(), 128);
+            assert!(emsa_pss_verify(&m_hash, &em, *em_bits, &params).is_ok());
+            assert!(emsa_pss_verify(&m_hash, &em, *em_bits, &PssParams::new(Hash::Sha256, Hash::Sha1, SaltLength::Auto)).is_ok());
+            assert!(emsa_pss_verify(&Hash::Sha256.digest(b"abd"), &em, *em_bits, &params).is_err());
+        }
+    }
+    let params = PssParams::new(Hash::Sha256, Hash::Sha256, SaltLength::Exact(20));
+    let em = emsa_pss_encode(&m_hash, 1023, &params).unwrap();
+    assert!(emsa_pss_verify(&m_hash, &em, 1023, &PssParams::new(Hash::Sha256, Hash::Sha256, SaltLength::Exact(32))).is_err());
+    assert!(emsa_pss_encode(&m_hash, 1023, &PssParams::new(Hash::Sha256, Hash::Sha256, SaltLength::Exact(95))).is_err());
+}
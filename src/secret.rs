@@ -0,0 +1,110 @@
+//! Secret
+//! Wrapper for the secret components of the keys: they're wiped on drop, redacted by `Debug` and can only
+//! be read through an explicit `expose_secret()` call.
+use num_bigint::BigUint;
+use std::fmt;
+use zeroize::Zeroize;
+use crate::helpers::ct;
+
+/// Values whose memory can be overwritten with zeros in place.
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+/// Values compared in constant time.
+pub trait ConstantTimeEq {
+    fn ct_eq(&self, other: &Self) -> bool;
+}
+
+impl Wipe for BigUint {
+    /// num-bigint doesn't give access to its digits, so they're overwritten with as many zeros through
+    /// `assign_from_slice`. Whether that writes over the same buffer is up to num-bigint, so it's a best
+    /// effort, and copies left by the arithmetic on intermediate values aren't covered.
+    fn wipe(&mut self) {
+        self.assign_from_slice(&vec![0; self.bits().div_ceil(32)]);
+        // Keeps the writes to a buffer about to be freed from being optimized away.
+        std::hint::black_box(&*self);
+    }
+}
+
+impl Wipe for Vec<u64> {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ConstantTimeEq for BigUint {
+    /// Both values are compared on as many limbs as the longest one, so the time only depends on their sizes.
+    fn ct_eq(&self, other: &Self) -> bool {
+        let len = self.bits().max(other.bits()).div_ceil(64);
+        let (mut a, mut b) = (ct::to_limbs(self, len), ct::to_limbs(other, len));
+        let eq = ct::eq(&a, &b);
+        a.wipe();
+        b.wipe();
+        eq
+    }
+}
+
+impl ConstantTimeEq for Vec<u64> {
+    fn ct_eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && ct::eq(self, other)
+    }
+}
+
+/// Secret value wiped on drop.
+#[derive(Clone)]
+pub struct Secret<T: Wipe>(T);
+
+impl<T: Wipe> Secret<T> {
+    pub fn new(secret: T) -> Self {
+        Secret(secret)
+    }
+
+    /// Gives access to the secret value. Copies of it aren't wiped unless they're secrets too.
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> From<T> for Secret<T> {
+    fn from(secret: T) -> Self {
+        Secret(secret)
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+/// Secrets are compared in constant time, so the comparison doesn't tell where they differ.
+impl<T: Wipe + ConstantTimeEq> PartialEq for Secret<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl<T: Wipe> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Secret([REDACTED])")
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn wipes_secrets() {
+    let mut x = BigUint::from_bytes_be(&[0xff; 40]);
+    x.wipe();
+    assert_eq!(x, BigUint::from(0u32));
+    let secret = Secret::new(BigUint::from(1234u32));
+    assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+    assert_eq!(secret.expose_secret(), &BigUint::from(1234u32));
+
+    let big = BigUint::from_bytes_be(&[0xff; 40]);
+    assert!(Secret::new(big.clone()) == Secret::new(big.clone()));
+    assert!(Secret::new(big.clone()) != Secret::new(&big - 1u32));
+    assert!(Secret::new(big) != secret);
+    assert!(Secret::new(BigUint::from(0u32)) == Secret::new(BigUint::from(0u32)));
+    assert!(Secret::new(vec![1u64, 2]) != Secret::new(vec![1u64]));
+}
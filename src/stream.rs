@@ -0,0 +1,126 @@
+//! Streaming encryption
+//! Hybrid encryption of `Read` sources into `Write` sinks with bounded memory. The data is split in chunks
+//! sealed with AES-256-GCM following the STREAM construction (Hoang et al., "Online Authenticated-Encryption
+//! and its Nonce-Reuse Misuse-Resistance"): each nonce holds the chunk counter and a flag marking the last
+//! chunk, so reordered, dropped or truncated chunks fail to authenticate.
+//!
+//! Stream layout (version 1), lengths in big-endian:
+//!
+//! | magic `RSAS` | version | chunk size (u32) | wrapped key len (u16) | wrapped key | nonce prefix (7) | chunks... |
+//!
+//! Every chunk but the last one holds `chunk size` bytes of plaintext plus the tag. The last one is shorter,
+//! possibly empty. The header is authenticated as additional data of every chunk.
+use std::io::{self, Read, Write};
+use rand::Rng;
+use aes_gcm::{Aes256Gcm, KeyInit};
+use aes_gcm::aead::{Aead, Payload};
+use crate::error::Error;
+use crate::types::*;
+use crate::hybrid::{wrap_params, gen_content_key, unwrap_content_key, KEY_LEN, NONCE_LEN, TAG_LEN};
+
+pub const MAGIC: &[u8] = b"RSAS";
+pub const VERSION: u8 = 1;
+/// Chunk size used when none is given: 64 KiB.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// Largest chunk size accepted when decrypting, which bounds the memory used: 16 MiB.
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+const NONCE_PREFIX_LEN: usize = 7;
+
+// Nonce of a chunk: prefix || counter (u32) || last chunk flag.
+fn chunk_nonce(prefix: &[u8], counter: u32, last: bool) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..NONCE_PREFIX_LEN].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_LEN..NONCE_LEN - 1].copy_from_slice(&counter.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last as u8;
+    nonce
+}
+
+// Fills the buffer from the reader, stopping early only at the end of the data. Returns the bytes read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(read)
+}
+
+impl PublicKey {
+    /// Encrypts everything read from `reader` into `writer` in authenticated chunks of `chunk_size` bytes.
+    pub fn encrypt_stream<R: Read, W: Write>(&self, mut reader: R, mut writer: W, chunk_size: usize) -> Result<(), Error> {
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {return Err(Error::InvalidParams("Invalid stream chunk size"))}
+        let key = gen_content_key();
+        let wrapped = self.encrypt_oaep(&key, &wrap_params())?;
+        let mut prefix = [0u8; NONCE_PREFIX_LEN];
+        rand::thread_rng().fill_bytes(&mut prefix);
+
+        let mut header = MAGIC.to_vec();
+        header.push(VERSION);
+        header.extend_from_slice(&(chunk_size as u32).to_be_bytes());
+        header.extend_from_slice(&(wrapped.len() as u16).to_be_bytes());
+        header.extend_from_slice(&wrapped);
+        header.extend_from_slice(&prefix);
+        writer.write_all(&header).map_err(Error::Io)?;
+
+        let cipher = Aes256Gcm::new(key[..].into());
+        let mut chunk = vec![0u8; chunk_size];
+        let mut counter = 0u32;
+        loop {
+            let len = read_full(&mut reader, &mut chunk).map_err(Error::Io)?;
+            // A short chunk is the last one. Data multiple of the chunk size ends with an empty chunk.
+            let last = len < chunk_size;
+            let nonce = chunk_nonce(&prefix, counter, last);
+            let sealed = cipher.encrypt((&nonce).into(), Payload { msg: &chunk[..len], aad: &header })
+                .map_err(|_| Error::MessageTooLong)?;
+            writer.write_all(&sealed).map_err(Error::Io)?;
+            if last {break}
+            counter = counter.checked_add(1).ok_or(Error::MessageTooLong)?;
+        }
+        writer.flush().map_err(Error::Io)
+    }
+}
+
+impl SecretKey {
+    /// Decrypts a stream created with `PublicKey::encrypt_stream` from `reader` into `writer`, one chunk at a time.
+    /// Chunks are written as soon as they're authenticated, so when an error is returned (tampering, truncation
+    /// or a wrong key) whatever was already written must be discarded.
+    pub fn decrypt_stream<R: Read, W: Write>(&self, mut reader: R, mut writer: W) -> Result<(), Error> {
+        let mut fixed = [0u8; 11];
+        if read_full(&mut reader, &mut fixed).map_err(Error::Io)? != fixed.len() || &fixed[..4] != MAGIC {
+            return Err(Error::Encoding("Invalid encrypted stream"))
+        }
+        if fixed[4] != VERSION {return Err(Error::Unsupported("Unsupported encrypted stream version"))}
+        let chunk_size = u32::from_be_bytes([fixed[5], fixed[6], fixed[7], fixed[8]]) as usize;
+        if chunk_size == 0 || chunk_size > MAX_CHUNK_SIZE {return Err(Error::Encoding("Invalid stream chunk size"))}
+        let wrapped_len = u16::from_be_bytes([fixed[9], fixed[10]]) as usize;
+
+        let mut header = fixed.to_vec();
+        header.resize(fixed.len() + wrapped_len + NONCE_PREFIX_LEN, 0);
+        if read_full(&mut reader, &mut header[fixed.len()..]).map_err(Error::Io)? != wrapped_len + NONCE_PREFIX_LEN {
+            return Err(Error::Encoding("Invalid encrypted stream"))
+        }
+        let key = unwrap_content_key(&header[fixed.len()..fixed.len() + wrapped_len], self, KEY_LEN, &wrap_params());
+        let prefix = header[header.len() - NONCE_PREFIX_LEN..].to_vec();
+
+        let cipher = Aes256Gcm::new(key[..].into());
+        let mut chunk = vec![0u8; chunk_size + TAG_LEN];
+        let mut counter = 0u32;
+        loop {
+            let len = read_full(&mut reader, &mut chunk).map_err(Error::Io)?;
+            // Only a full chunk can be followed by more; anything shorter must be the last chunk.
+            let last = len < chunk.len();
+            let nonce = chunk_nonce(&prefix, counter, last);
+            let plaintext = cipher.decrypt((&nonce).into(), Payload { msg: &chunk[..len], aad: &header })
+                .map_err(|_| Error::Decryption)?;
+            writer.write_all(&plaintext).map_err(Error::Io)?;
+            if last {break}
+            counter = counter.checked_add(1).ok_or(Error::Decryption)?;
+        }
+        writer.flush().map_err(Error::Io)
+    }
+}